@@ -1,6 +1,8 @@
+use crate::SheetRegion;
+
 pub struct Animation {
-    // states are sprite sheet positions
-    pub states: Vec<[f32; 6]>,
+    // states are sprite sheet regions, drawn facing right
+    pub states: Vec<SheetRegion>,
     // frame counter is how many frames have passed on the current animation state
     pub frame_counter: i32,
     // rate is how many frames need to pass to go to the next animation state
@@ -9,7 +11,6 @@ pub struct Animation {
     pub state_number: usize,
 
     pub is_facing_left: bool,
-    pub sprite_width: f32,
 
     pub is_looping: bool,
     pub is_done: bool,
@@ -49,27 +50,14 @@ impl Animation {
             self.tick();
         }
     }
-    pub fn get_current_state(&mut self) -> [f32; 6]{
+    pub fn get_current_state(&mut self) -> SheetRegion{
 
         if !self.is_looping && self.state_number > self.states.len() - 1 {
             self.state_number = self.states.len() - 1;
 
         }
-        if self.is_facing_left{
-            if self.states[self.state_number][2] > 0.0 {
-                self.states[self.state_number][2] *= -1.0;
-                self.states[self.state_number][0] += self.sprite_width;
-            }
-              
-        }
-        else {
-            if self.states[self.state_number][2] < 0.0 {
-                self.states[self.state_number][2] *= -1.0;
-                self.states[self.state_number][0] -= self.sprite_width;
-            }            
-        }
-
-        return self.states[self.state_number]
+        // facing left is just the same frame mirrored
+        self.states[self.state_number].flip_x(self.is_facing_left)
     }
 
     pub fn apply_face_left(&mut self){
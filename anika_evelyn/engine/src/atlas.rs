@@ -0,0 +1,449 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::geom::Vec2;
+use crate::{SheetRegion, Transform};
+
+// Loads spritesheets packed by TexturePacker in its "JSON (Hash)" format,
+// so games can look sprites up by name instead of hard-coding pixel rectangles.
+// Frame names are stored without their file extension, so "bearWalk1.png"
+// is looked up as "bearWalk1"; two frames whose names differ only by their
+// extensions can't both be loaded.
+//
+// Rotated frames become rotated SheetRegions, which draw upright. Trimmed
+// frames have their transparent edges cut off, so their region is smaller
+// than the original image; use Frame::place to draw them at the right spot.
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    // a frame the game asked for isn't in the atlas
+    MissingFrame(String),
+    // a frame is too far into or too big for the sheet to fit in a SheetRegion
+    FrameOutOfRange(String),
+    // two frames have the same name once their extensions are dropped,
+    // e.g. "fire.png" and "fire.jpg"
+    DuplicateFrame(String),
+}
+
+impl std::fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtlasError::Io(e) => write!(f, "couldn't read atlas: {}", e),
+            AtlasError::Json(e) => write!(f, "couldn't parse atlas: {}", e),
+            AtlasError::MissingFrame(name) => write!(f, "atlas has no frame named {:?}", name),
+            AtlasError::FrameOutOfRange(name) => {
+                write!(f, "atlas frame {:?} doesn't fit in a SheetRegion", name)
+            }
+            AtlasError::DuplicateFrame(name) => {
+                write!(f, "atlas has more than one frame named {:?}", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AtlasError::Io(e) => Some(e),
+            AtlasError::Json(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for AtlasError {
+    fn from(e: std::io::Error) -> Self {
+        AtlasError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for AtlasError {
+    fn from(e: serde_json::Error) -> Self {
+        AtlasError::Json(e)
+    }
+}
+
+// just the parts of the TexturePacker format we use
+#[derive(Deserialize)]
+struct AtlasFile {
+    frames: HashMap<String, FrameData>,
+    meta: MetaData,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FrameData {
+    frame: RectData,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    sprite_source_size: Option<RectData>,
+    source_size: Option<SizeData>,
+}
+
+#[derive(Deserialize)]
+struct RectData {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct MetaData {
+    image: String,
+    size: SizeData,
+}
+
+#[derive(Deserialize)]
+struct SizeData {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    // where the (possibly trimmed and rotated) pixels are in the sheet
+    pub region: SheetRegion,
+    // the size in pixels of the original image, before trimming
+    pub source_size: Vec2,
+    // the trimmed rectangle within the original image, measured in pixels
+    // from its top left corner; the whole image if it wasn't trimmed
+    pub trim_corner: Vec2,
+    pub trim_size: Vec2,
+}
+
+impl Frame {
+    // Given the transform the whole, untrimmed image would be drawn with,
+    // the transform to draw just the trimmed region with.
+    pub fn place(&self, trf: Transform) -> Transform {
+        let size: Vec2 = trf.size().into();
+        let scale = size / self.source_size;
+        // how far the trimmed center is from the image center, in image pixels (y down)
+        let offset = self.trim_corner + self.trim_size / 2.0 - self.source_size / 2.0;
+        let offset = Vec2::new(offset.x, -offset.y) * scale;
+        let center = Vec2::from(trf.translation()) + Vec2::from_angle(trf.rot).rotate(offset);
+        Transform::new(center.into(), (self.trim_size * scale).into(), trf.rot)
+    }
+    // Mirror this frame, including where its trimmed region sits.
+    pub fn flip_x(self, flip: bool) -> Self {
+        if flip == self.region.is_flipped_x() {
+            return self;
+        }
+        Frame {
+            region: self.region.flip_x(flip),
+            trim_corner: Vec2::new(
+                self.source_size.x - self.trim_corner.x - self.trim_size.x,
+                self.trim_corner.y,
+            ),
+            ..self
+        }
+    }
+    pub fn flip_y(self, flip: bool) -> Self {
+        if flip == self.region.is_flipped_y() {
+            return self;
+        }
+        Frame {
+            region: self.region.flip_y(flip),
+            trim_corner: Vec2::new(
+                self.trim_corner.x,
+                self.source_size.y - self.trim_corner.y - self.trim_size.y,
+            ),
+            ..self
+        }
+    }
+}
+
+pub struct Atlas {
+    // the spritesheet image file named by the atlas
+    pub image: String,
+    // the size of the spritesheet image in pixels
+    pub size: (u32, u32),
+    frames: HashMap<String, Frame>,
+}
+
+impl Atlas {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AtlasError> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json)
+    }
+    pub fn from_json(json: &str) -> Result<Self, AtlasError> {
+        let file: AtlasFile = serde_json::from_str(json)?;
+        let mut frames = HashMap::with_capacity(file.frames.len());
+        for (name, data) in file.frames {
+            let name = match name.rsplit_once('.') {
+                Some((stem, _ext)) => stem.to_string(),
+                None => name,
+            };
+            let RectData { x, y, w, h } = data.frame;
+            // frame's w and h are upright, but rotated frames sit sideways in the sheet
+            let (sheet_w, sheet_h) = if data.rotated { (h, w) } else { (w, h) };
+            let fits = |v: u32| u16::try_from(v).ok();
+            let region = match (fits(x), fits(y), fits(sheet_w), fits(sheet_h)) {
+                (Some(x), Some(y), Some(w), Some(h)) => {
+                    SheetRegion::rect(x, y, w, h).rotated(data.rotated)
+                }
+                _ => return Err(AtlasError::FrameOutOfRange(name)),
+            };
+            let trim_size = Vec2::new(w as f32, h as f32);
+            let (source_size, trim_corner) =
+                match (data.trimmed, data.source_size, data.sprite_source_size) {
+                    (true, Some(source), Some(trim)) => (
+                        Vec2::new(source.w as f32, source.h as f32),
+                        Vec2::new(trim.x as f32, trim.y as f32),
+                    ),
+                    _ => (trim_size, Vec2::ZERO),
+                };
+            match frames.entry(name) {
+                Entry::Occupied(entry) => {
+                    return Err(AtlasError::DuplicateFrame(entry.key().clone()))
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(Frame {
+                        region,
+                        source_size,
+                        trim_corner,
+                        trim_size,
+                    });
+                }
+            }
+        }
+        Ok(Atlas {
+            image: file.meta.image,
+            size: (file.meta.size.w, file.meta.size.h),
+            frames,
+        })
+    }
+    // The region for a frame on sheet 0 at depth 0; use SheetRegion::sheet and
+    // SheetRegion::depth to change those.
+    // If the frame was trimmed, draw it with Atlas::frame and Frame::place instead.
+    pub fn region(&self, name: &str) -> Result<SheetRegion, AtlasError> {
+        self.frame(name).map(|frame| frame.region)
+    }
+    pub fn get(&self, name: &str) -> Option<SheetRegion> {
+        self.frames.get(name).map(|frame| frame.region)
+    }
+    pub fn frame(&self, name: &str) -> Result<Frame, AtlasError> {
+        self.frames
+            .get(name)
+            .copied()
+            .ok_or_else(|| AtlasError::MissingFrame(name.to_string()))
+    }
+    // Regions for several frames at once, e.g. the frames of an animation.
+    pub fn regions<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Vec<SheetRegion>, AtlasError> {
+        names.into_iter().map(|name| self.region(name)).collect()
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.frames.keys().map(|name| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Atlas, AtlasError};
+    use crate::geom::Vec2;
+    use crate::Transform;
+
+    // a 64x32 sheet with a plain frame, a rotated one, and a trimmed one
+    const SHEET: &str = r#"{
+        "frames": {
+            "plain.png": {
+                "frame": {"x": 0, "y": 0, "w": 16, "h": 16},
+                "rotated": false,
+                "trimmed": false,
+                "spriteSourceSize": {"x": 0, "y": 0, "w": 16, "h": 16},
+                "sourceSize": {"w": 16, "h": 16}
+            },
+            "sideways.png": {
+                "frame": {"x": 16, "y": 0, "w": 8, "h": 20},
+                "rotated": true,
+                "trimmed": false
+            },
+            "trimmed.png": {
+                "frame": {"x": 40, "y": 4, "w": 10, "h": 6},
+                "rotated": false,
+                "trimmed": true,
+                "spriteSourceSize": {"x": 2, "y": 1, "w": 10, "h": 6},
+                "sourceSize": {"w": 16, "h": 16}
+            }
+        },
+        "meta": {"image": "sheet.png", "size": {"w": 64, "h": 32}}
+    }"#;
+
+    #[test]
+    fn parses_frames_without_their_extensions() {
+        let atlas = Atlas::from_json(SHEET).unwrap();
+        assert_eq!(atlas.image, "sheet.png");
+        assert_eq!(atlas.size, (64, 32));
+        let mut names: Vec<_> = atlas.names().collect();
+        names.sort();
+        assert_eq!(names, ["plain", "sideways", "trimmed"]);
+        let plain = atlas.frame("plain").unwrap();
+        assert_eq!(plain.region.x, 0);
+        assert_eq!(plain.region.y, 0);
+        assert_eq!(plain.region.w, 16);
+        assert_eq!(plain.region.h, 16);
+        assert!(!plain.region.is_rotated());
+        assert_eq!(plain.source_size, Vec2::new(16.0, 16.0));
+        assert_eq!(plain.trim_corner, Vec2::ZERO);
+        assert_eq!(plain.trim_size, Vec2::new(16.0, 16.0));
+        assert!(atlas.get("plain.png").is_none());
+    }
+
+    #[test]
+    fn rotated_frames_swap_width_and_height_in_the_sheet() {
+        let atlas = Atlas::from_json(SHEET).unwrap();
+        let sideways = atlas.frame("sideways").unwrap();
+        // the frame is 8x20 upright, so it takes up 20x8 pixels lying on its side
+        assert!(sideways.region.is_rotated());
+        assert_eq!(sideways.region.x, 16);
+        assert_eq!(sideways.region.y, 0);
+        assert_eq!(sideways.region.w, 20);
+        assert_eq!(sideways.region.h, 8);
+        assert_eq!(sideways.source_size, Vec2::new(8.0, 20.0));
+        assert_eq!(sideways.trim_size, Vec2::new(8.0, 20.0));
+    }
+
+    #[test]
+    fn trimmed_frames_keep_their_offset_in_the_source_image() {
+        let atlas = Atlas::from_json(SHEET).unwrap();
+        let trimmed = atlas.frame("trimmed").unwrap();
+        assert_eq!(trimmed.region.x, 40);
+        assert_eq!(trimmed.region.y, 4);
+        assert_eq!(trimmed.region.w, 10);
+        assert_eq!(trimmed.region.h, 6);
+        assert_eq!(trimmed.source_size, Vec2::new(16.0, 16.0));
+        assert_eq!(trimmed.trim_corner, Vec2::new(2.0, 1.0));
+        assert_eq!(trimmed.trim_size, Vec2::new(10.0, 6.0));
+    }
+
+    #[test]
+    fn missing_frames_are_errors() {
+        let atlas = Atlas::from_json(SHEET).unwrap();
+        assert!(matches!(
+            atlas.region("nope"),
+            Err(AtlasError::MissingFrame(name)) if name == "nope"
+        ));
+        assert!(matches!(
+            atlas.regions(["plain", "nope"]),
+            Err(AtlasError::MissingFrame(_))
+        ));
+    }
+
+    #[test]
+    fn bad_json_is_an_error() {
+        assert!(matches!(
+            Atlas::from_json("{\"frames\": {}}"),
+            Err(AtlasError::Json(_))
+        ));
+        assert!(matches!(
+            Atlas::from_json("not json"),
+            Err(AtlasError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn frames_too_big_for_a_sheet_region_are_errors() {
+        let json = r#"{
+            "frames": {"huge.png": {"frame": {"x": 70000, "y": 0, "w": 4, "h": 4}}},
+            "meta": {"image": "sheet.png", "size": {"w": 80000, "h": 4}}
+        }"#;
+        assert!(matches!(
+            Atlas::from_json(json),
+            Err(AtlasError::FrameOutOfRange(name)) if name == "huge"
+        ));
+    }
+
+    #[test]
+    fn names_that_differ_only_by_extension_are_errors() {
+        let json = r#"{
+            "frames": {
+                "fire.png": {"frame": {"x": 0, "y": 0, "w": 4, "h": 4}},
+                "fire.jpg": {"frame": {"x": 4, "y": 0, "w": 4, "h": 4}}
+            },
+            "meta": {"image": "sheet.png", "size": {"w": 8, "h": 4}}
+        }"#;
+        assert!(matches!(
+            Atlas::from_json(json),
+            Err(AtlasError::DuplicateFrame(name)) if name == "fire"
+        ));
+    }
+
+    #[test]
+    fn place_moves_trimmed_frames_to_where_they_were_in_the_source() {
+        let atlas = Atlas::from_json(SHEET).unwrap();
+        let trimmed = atlas.frame("trimmed").unwrap();
+        // the whole 16x16 image drawn at twice its size
+        let whole = Transform::new([100.0, 200.0], [32.0, 32.0], 0.0);
+        // the trimmed 10x6 rectangle's center is 1 pixel left of and 4 pixels
+        // above the image's center, and y points up in the world
+        let placed = trimmed.place(whole);
+        assert_eq!(placed.translation(), [98.0, 208.0]);
+        assert_eq!(placed.size(), [20.0, 12.0]);
+        assert_eq!(placed.rot, 0.0);
+        // untrimmed frames fill the whole transform
+        let plain = atlas.frame("plain").unwrap().place(whole);
+        assert_eq!(plain.translation(), [100.0, 200.0]);
+        assert_eq!(plain.size(), [32.0, 32.0]);
+    }
+
+    #[test]
+    fn place_turns_the_trim_offset_with_the_transform() {
+        let atlas = Atlas::from_json(SHEET).unwrap();
+        let trimmed = atlas.frame("trimmed").unwrap();
+        let whole = Transform::new([100.0, 200.0], [32.0, 32.0], std::f32::consts::FRAC_PI_2);
+        let placed = trimmed.place(whole);
+        // a quarter turn counterclockwise takes the (-2, 8) offset to (-8, -2)
+        let [x, y] = placed.translation();
+        assert!((x - 92.0).abs() < 1e-4, "x is {}", x);
+        assert!((y - 198.0).abs() < 1e-4, "y is {}", y);
+        assert_eq!(placed.size(), [20.0, 12.0]);
+        assert_eq!(placed.rot, std::f32::consts::FRAC_PI_2);
+    }
+
+    #[test]
+    fn flipping_a_trimmed_frame_mirrors_its_offset() {
+        let atlas = Atlas::from_json(SHEET).unwrap();
+        let trimmed = atlas.frame("trimmed").unwrap();
+        let whole = Transform::new([100.0, 200.0], [32.0, 32.0], 0.0);
+
+        let unflipped = trimmed.flip_x(false).flip_y(false);
+        assert!(!unflipped.region.is_flipped_x());
+        assert!(!unflipped.region.is_flipped_y());
+        assert_eq!(unflipped.trim_corner, Vec2::new(2.0, 1.0));
+        assert_eq!(unflipped.place(whole).translation(), [98.0, 208.0]);
+
+        let flipped_x = trimmed.flip_x(true);
+        assert!(flipped_x.region.is_flipped_x());
+        assert!(!flipped_x.region.is_flipped_y());
+        assert_eq!(flipped_x.trim_corner, Vec2::new(4.0, 1.0));
+        assert_eq!(flipped_x.place(whole).translation(), [102.0, 208.0]);
+
+        let flipped_y = trimmed.flip_y(true);
+        assert!(!flipped_y.region.is_flipped_x());
+        assert!(flipped_y.region.is_flipped_y());
+        assert_eq!(flipped_y.trim_corner, Vec2::new(2.0, 9.0));
+        assert_eq!(flipped_y.place(whole).translation(), [98.0, 192.0]);
+
+        let flipped_both = trimmed.flip_x(true).flip_y(true);
+        assert!(flipped_both.region.is_flipped_x());
+        assert!(flipped_both.region.is_flipped_y());
+        assert_eq!(flipped_both.place(whole).translation(), [102.0, 192.0]);
+        assert_eq!(flipped_both.place(whole).size(), [20.0, 12.0]);
+
+        // flipping back puts it where it started
+        let unflipped = flipped_both.flip_x(false).flip_y(false);
+        assert!(!unflipped.region.is_flipped_x());
+        assert!(!unflipped.region.is_flipped_y());
+        assert_eq!(unflipped.trim_corner, Vec2::new(2.0, 1.0));
+    }
+}
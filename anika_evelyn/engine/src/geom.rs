@@ -17,43 +17,25 @@ pub struct AABB {
 
 impl From<AABB> for Transform {
     fn from(val: AABB) -> Self {
-        Transform {
-            w: val.size.x as u16,
-            h: val.size.y as u16,
-            x: val.center.x,
-            y: val.center.y,
-            rot: 0.0,
-        }
+        Transform::new(val.center.into(), val.size.into(), 0.0)
     }
 }
 
 impl From<Rect> for Transform {
     fn from(val: Rect) -> Self {
-        Transform {
-            w: val.size.x as u16,
-            h: val.size.y as u16,
-            x: val.corner.x + val.size.x / 2.0,
-            y: val.corner.y + val.size.y / 2.0,
-            rot: 0.0,
-        }
+        Transform::new((val.corner + val.size / 2.0).into(), val.size.into(), 0.0)
     }
 }
 
 impl From<Rect> for GPUCamera {
     fn from(val: Rect) -> Self {
-        GPUCamera {
-            screen_pos: val.corner.into(),
-            screen_size: val.size.into(),
-        }
+        GPUCamera::new(val.corner.into(), val.size.into())
     }
 }
 
 impl From<AABB> for GPUCamera {
     fn from(val: AABB) -> Self {
-        GPUCamera {
-            screen_pos: (val.center - val.size / 2.0).into(),
-            screen_size: val.size.into(),
-        }
+        GPUCamera::new((val.center - val.size / 2.0).into(), val.size.into())
     }
 }
 
@@ -1,8 +1,13 @@
 pub use bytemuck::Zeroable;
 pub use frenderer::{
     input::{Input, Key},
-    wgpu, BitFont, Frenderer, GPUCamera as Camera, SheetRegion, Transform,
+    materials, sampler_descriptor, wgpu, BitFont, BlendMode, Color, Frenderer, GPUCamera as Camera,
+    MaterialId, NineSlice, PostEffect, Presentation, SheetRegion, SpriteGroupId, TilemapId,
+    Tileset, Transform, Transition, WipeDirection,
 };
+// how many seconds each call to Game::update covers
+pub const DT: f32 = 1.0 / 60.0;
+
 pub trait Game: Sized + 'static {
     fn new(engine: &mut Engine) -> Self;
     fn update(&mut self, engine: &mut Engine);
@@ -31,7 +36,6 @@ impl Engine {
     }
     pub fn run<G: Game>(mut self) {
         let mut game = G::new(&mut self);
-        const DT: f32 = 1.0 / 60.0;
         const DT_FUDGE_AMOUNT: f32 = 0.0002;
         const DT_MAX: f32 = DT * 5.0;
         const TIME_SNAPS: [f32; 5] = [15.0, 30.0, 60.0, 120.0, 144.0];
@@ -72,6 +76,7 @@ impl Engine {
                             // simulate a frame
                             acc -= DT;
                             game.update(&mut self);
+                            self.renderer.update_transition(DT);
                             self.input.next_frame();
                         }
                         game.render(&mut self);
@@ -97,3 +102,5 @@ pub mod geom;
 pub mod animation;
 pub mod gamestate;
 pub mod action;
+pub mod atlas;
+pub mod particles;
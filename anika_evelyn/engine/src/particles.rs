@@ -0,0 +1,538 @@
+use crate::geom::Vec2;
+use crate::{wgpu, BlendMode, Camera, Color, Frenderer, SheetRegion, SpriteGroupId, Transform};
+use bytemuck::Zeroable;
+use rand::rngs::ThreadRng;
+use rand::Rng;
+use std::cmp::Reverse;
+use std::ops::Range;
+
+// where an emitter's particles appear, relative to its position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmitterShape {
+    Point,
+    // anywhere inside a circle around the position
+    Circle { radius: f32 },
+    // anywhere inside a rectangle centered on the position
+    Rect { size: Vec2 },
+    // anywhere along the line from the position to position + end
+    Line { end: Vec2 },
+}
+
+impl EmitterShape {
+    fn sample(&self, rng: &mut impl Rng) -> Vec2 {
+        match *self {
+            EmitterShape::Point => Vec2::ZERO,
+            EmitterShape::Circle { radius } => {
+                // the square root spreads points evenly instead of bunching them in the middle
+                let r = radius * rng.gen::<f32>().sqrt();
+                Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU)) * r
+            }
+            EmitterShape::Rect { size } => {
+                Vec2::new(rng.gen::<f32>() - 0.5, rng.gen::<f32>() - 0.5) * size
+            }
+            EmitterShape::Line { end } => end * rng.gen::<f32>(),
+        }
+    }
+}
+
+// describes what particles an emitter makes and how they behave over their lives.
+// start from Emitter::default() and fill in what you need.
+#[derive(Clone, Debug)]
+pub struct Emitter {
+    pub position: Vec2,
+    pub shape: EmitterShape,
+    // particles spawned per second while active; bursts don't need a rate
+    pub rate: f32,
+    pub active: bool,
+    // how many seconds each particle lives, picked at random from the range
+    pub lifetime: Range<f32>,
+    // how fast particles start moving, in world units per second
+    pub speed: Range<f32>,
+    // the direction particles start moving, in radians counterclockwise from +x,
+    // and how far to either side of it they may stray
+    pub direction: f32,
+    pub spread: f32,
+    // added to each particle's velocity every second (negative y falls, positive y rises)
+    pub gravity: Vec2,
+    // tints and sizes spread evenly over a particle's life and blended between;
+    // a single entry stays the same for the whole life
+    pub colors: Vec<Color>,
+    pub sizes: Vec<Vec2>,
+    // animation frames, spread evenly over a particle's life unless fps is above 0,
+    // in which case they loop at that many frames per second
+    pub frames: Vec<SheetRegion>,
+    pub fps: f32,
+    // turns each particle to face the way it's moving, e.g. for sparks
+    pub align_to_velocity: bool,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            shape: EmitterShape::Point,
+            rate: 0.0,
+            active: true,
+            lifetime: 1.0..1.0,
+            speed: 0.0..0.0,
+            direction: std::f32::consts::FRAC_PI_2,
+            spread: 0.0,
+            gravity: Vec2::ZERO,
+            colors: vec![Color::WHITE],
+            sizes: vec![Vec2::ONE],
+            frames: Vec::new(),
+            fps: 0.0,
+            align_to_velocity: false,
+        }
+    }
+}
+
+// why an emitter was turned away by ParticleSystem::add_emitter or set_emitter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmitterError {
+    NoFrames,
+    NoColors,
+    NoSizes,
+}
+
+impl std::fmt::Display for EmitterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmitterError::NoFrames => write!(f, "particle emitter has no frames"),
+            EmitterError::NoColors => write!(f, "particle emitter has no colors"),
+            EmitterError::NoSizes => write!(f, "particle emitter has no sizes"),
+        }
+    }
+}
+
+impl std::error::Error for EmitterError {}
+
+impl Emitter {
+    // every particle needs a frame, a color, and a size; the system only takes emitters that pass
+    fn check(&self) -> Result<(), EmitterError> {
+        if self.frames.is_empty() {
+            Err(EmitterError::NoFrames)
+        } else if self.colors.is_empty() {
+            Err(EmitterError::NoColors)
+        } else if self.sizes.is_empty() {
+            Err(EmitterError::NoSizes)
+        } else {
+            Ok(())
+        }
+    }
+    fn frame_at(&self, age: f32, t: f32) -> SheetRegion {
+        let count = self.frames.len();
+        let frame = if self.fps > 0.0 {
+            (age * self.fps) as usize % count
+        } else {
+            ((t * count as f32) as usize).min(count - 1)
+        };
+        self.frames[frame]
+    }
+    fn color_at(&self, t: f32) -> Color {
+        blend_keys(&self.colors, t, lerp_color)
+    }
+    fn size_at(&self, t: f32) -> Vec2 {
+        blend_keys(&self.sizes, t, Vec2::lerp)
+    }
+}
+
+// a handle to an emitter added to a ParticleSystem
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmitterId(usize);
+
+#[derive(Clone, Copy)]
+struct Particle {
+    pos: Vec2,
+    vel: Vec2,
+    age: f32,
+    lifetime: f32,
+    emitter: usize,
+}
+
+// owns one sprite group and draws every live particle from its emitters into it.
+// particles live in world space, so give the group the same camera as the rest of the world.
+pub struct ParticleSystem {
+    group: SpriteGroupId,
+    pool: Pool,
+    // how many sprites were filled in last time, so ones no longer needed can be cleared
+    drawn: usize,
+}
+
+impl ParticleSystem {
+    // makes a sprite group with room for `capacity` particles drawn from `tex`.
+    // once it's full, new particles aren't spawned until old ones die.
+    pub fn new(renderer: &mut Frenderer, tex: &wgpu::Texture, capacity: usize, camera: Camera) -> Self {
+        let group = renderer.sprites.add_sprite_group(
+            &renderer.gpu,
+            tex,
+            vec![Transform::zeroed(); capacity],
+            vec![SheetRegion::zeroed(); capacity],
+            camera,
+        );
+        // particles fade in and out, so blend them; draw keeps them back to front itself,
+        // so the renderer only has to upload the live ones instead of re-sorting the whole pool
+        renderer.sprites.set_blend_mode(&renderer.gpu, group, BlendMode::AlphaUnsorted);
+        Self {
+            group,
+            pool: Pool::new(capacity),
+            drawn: 0,
+        }
+    }
+    pub fn group(&self) -> SpriteGroupId {
+        self.group
+    }
+    // emitters last as long as the system; set active to false to stop one
+    pub fn add_emitter(&mut self, emitter: Emitter) -> Result<EmitterId, EmitterError> {
+        self.pool.add_emitter(emitter)
+    }
+    pub fn emitter(&self, id: EmitterId) -> &Emitter {
+        &self.pool.emitters[id.0]
+    }
+    // replaces an emitter, keeping the old one if the new one is missing frames, colors, or sizes.
+    // particles it already spawned carry on with its new settings.
+    pub fn set_emitter(&mut self, id: EmitterId, emitter: Emitter) -> Result<(), EmitterError> {
+        emitter.check()?;
+        self.pool.emitters[id.0] = emitter;
+        Ok(())
+    }
+    pub fn move_emitter(&mut self, id: EmitterId, position: Vec2) {
+        self.pool.emitters[id.0].position = position;
+    }
+    // spawns `count` particles from an emitter right away, whether it's active or not
+    pub fn burst(&mut self, id: EmitterId, count: usize) {
+        self.pool.spawn(id.0, count);
+    }
+    // how many particles are alive
+    pub fn len(&self) -> usize {
+        self.pool.particles.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pool.particles.is_empty()
+    }
+    // kills every particle; they disappear the next time the system is drawn
+    pub fn clear(&mut self) {
+        self.pool.clear();
+    }
+    // spawns new particles and moves the live ones forward by `dt` seconds
+    pub fn update(&mut self, dt: f32) {
+        self.pool.update(dt);
+    }
+    // writes every live particle into the sprite group, clearing sprites left over from last time
+    pub fn draw(&mut self, renderer: &mut Frenderer) {
+        let live = self.pool.particles.len();
+        let used = live.max(self.drawn);
+        // nothing is alive now or was last time, so leave the group alone
+        if used == 0 {
+            return;
+        }
+        self.pool.sort_back_to_front();
+        let emitters = &self.pool.emitters;
+        let particles = &self.pool.particles;
+        let (trfs, uvs) = renderer.sprites.get_sprite_range_mut(self.group, 0..used);
+        for ((p, trf), uv) in particles.iter().zip(trfs.iter_mut()).zip(uvs.iter_mut()) {
+            let emitter = &emitters[p.emitter];
+            let t = p.age / p.lifetime;
+            let rot = if emitter.align_to_velocity {
+                p.vel.y.atan2(p.vel.x)
+            } else {
+                0.0
+            };
+            *trf = Transform::new(p.pos.into(), emitter.size_at(t).into(), rot);
+            *uv = emitter.frame_at(p.age, t);
+        }
+        for i in live..used {
+            trfs[i] = Transform::zeroed();
+            uvs[i] = SheetRegion::zeroed();
+        }
+        let colors = renderer.sprites.get_color_range_mut(self.group, 0..live);
+        for (p, color) in particles.iter().zip(colors.iter_mut()) {
+            *color = emitters[p.emitter].color_at(p.age / p.lifetime);
+        }
+        self.drawn = live;
+    }
+}
+
+// the emitters and live particles of a ParticleSystem, kept apart from its sprite group
+struct Pool {
+    capacity: usize,
+    emitters: Vec<Emitter>,
+    // the fraction of a particle each emitter still owes from earlier updates
+    owed: Vec<f32>,
+    particles: Vec<Particle>,
+    rng: ThreadRng,
+}
+
+impl Pool {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            emitters: Vec::new(),
+            owed: Vec::new(),
+            particles: Vec::with_capacity(capacity),
+            rng: rand::thread_rng(),
+        }
+    }
+    fn add_emitter(&mut self, emitter: Emitter) -> Result<EmitterId, EmitterError> {
+        emitter.check()?;
+        self.emitters.push(emitter);
+        self.owed.push(0.0);
+        Ok(EmitterId(self.emitters.len() - 1))
+    }
+    fn clear(&mut self) {
+        self.particles.clear();
+        self.owed.fill(0.0);
+    }
+    fn update(&mut self, dt: f32) {
+        let emitters = &self.emitters;
+        self.particles.retain_mut(|p| {
+            p.age += dt;
+            if p.age >= p.lifetime {
+                return false;
+            }
+            p.vel += emitters[p.emitter].gravity * dt;
+            p.pos += p.vel * dt;
+            true
+        });
+        for index in 0..self.emitters.len() {
+            let emitter = &self.emitters[index];
+            if !emitter.active || emitter.rate <= 0.0 {
+                self.owed[index] = 0.0;
+                continue;
+            }
+            self.owed[index] += emitter.rate * dt;
+            let count = self.owed[index] as usize;
+            self.owed[index] -= count as f32;
+            self.spawn(index, count);
+        }
+    }
+    // spawns up to `count` particles from an emitter, as many as there's room for
+    fn spawn(&mut self, index: usize, count: usize) {
+        let emitter = &self.emitters[index];
+        let rng = &mut self.rng;
+        let count = count.min(self.capacity - self.particles.len());
+        for _ in 0..count {
+            let angle = emitter.direction + pick(rng, &(-emitter.spread..emitter.spread));
+            self.particles.push(Particle {
+                pos: emitter.position + emitter.shape.sample(rng),
+                vel: Vec2::from_angle(angle) * pick(rng, &emitter.speed),
+                age: 0.0,
+                lifetime: pick(rng, &emitter.lifetime).max(f32::EPSILON),
+                emitter: index,
+            });
+        }
+    }
+    // orders particles from farthest to nearest by their current frame's depth.  the sort is
+    // stable and particles are mostly in order already, so this is cheap, and particles at the
+    // same depth stay oldest first so newer ones are drawn on top
+    fn sort_back_to_front(&mut self) {
+        let emitters = &self.emitters;
+        self.particles.sort_by_key(|p| {
+            Reverse(emitters[p.emitter].frame_at(p.age, p.age / p.lifetime).depth)
+        });
+    }
+}
+
+// a random number in the range, or its start if the range is empty
+fn pick(rng: &mut impl Rng, range: &Range<f32>) -> f32 {
+    if range.start < range.end {
+        rng.gen_range(range.clone())
+    } else {
+        range.start
+    }
+}
+
+// blends between the keys, spread evenly from t = 0 to t = 1
+fn blend_keys<T: Copy>(keys: &[T], t: f32, lerp: impl Fn(T, T, f32) -> T) -> T {
+    if keys.len() == 1 {
+        return keys[0];
+    }
+    let x = t.clamp(0.0, 1.0) * (keys.len() - 1) as f32;
+    let i = (x as usize).min(keys.len() - 2);
+    lerp(keys[i], keys[i + 1], x - i as f32)
+}
+
+fn lerp_color(a: Color, b: Color, t: f32) -> Color {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color::rgba(mix(a.r, b.r), mix(a.g, b.g), mix(a.b, b.b), mix(a.a, b.a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Emitter, EmitterError, Pool};
+    use crate::geom::Vec2;
+    use crate::{Color, SheetRegion};
+
+    #[test]
+    fn emitters_spawn_at_their_rate_and_carry_fractions_over() {
+        let mut pool = Pool::new(64);
+        pool.add_emitter(Emitter {
+            rate: 10.0,
+            lifetime: 10.0..10.0,
+            frames: vec![SheetRegion::new(0, 0, 0, 0, 8, 8)],
+            ..Emitter::default()
+        })
+        .unwrap();
+        // 2.5 particles are due, so 2 spawn and half of one is owed
+        pool.update(0.25);
+        assert_eq!(pool.particles.len(), 2);
+        pool.update(0.25);
+        assert_eq!(pool.particles.len(), 5);
+        pool.update(1.0);
+        assert_eq!(pool.particles.len(), 15);
+    }
+
+    #[test]
+    fn inactive_emitters_spawn_nothing_and_forget_what_they_owed() {
+        let mut pool = Pool::new(64);
+        pool.add_emitter(Emitter {
+            rate: 10.0,
+            lifetime: 10.0..10.0,
+            frames: vec![SheetRegion::new(0, 0, 0, 0, 8, 8)],
+            ..Emitter::default()
+        })
+        .unwrap();
+        pool.update(0.25);
+        pool.emitters[0].active = false;
+        pool.update(1.0);
+        assert_eq!(pool.particles.len(), 2);
+        // the half particle owed from before was dropped
+        pool.emitters[0].active = true;
+        pool.update(0.05);
+        assert_eq!(pool.particles.len(), 2);
+    }
+
+    #[test]
+    fn particles_die_at_the_end_of_their_lifetime() {
+        let mut pool = Pool::new(64);
+        pool.add_emitter(Emitter {
+            lifetime: 1.0..1.0,
+            speed: 2.0..2.0,
+            direction: 0.0,
+            frames: vec![SheetRegion::new(0, 0, 0, 0, 8, 8)],
+            ..Emitter::default()
+        })
+        .unwrap();
+        pool.spawn(0, 3);
+        pool.update(0.5);
+        assert_eq!(pool.particles.len(), 3);
+        assert_eq!(pool.particles[0].age, 0.5);
+        assert!((pool.particles[0].pos - Vec2::new(1.0, 0.0)).length() < 1e-5);
+        pool.update(0.5);
+        assert!(pool.particles.is_empty());
+    }
+
+    #[test]
+    fn a_full_pool_reuses_the_room_of_dead_particles() {
+        let mut pool = Pool::new(4);
+        let short = pool
+            .add_emitter(Emitter {
+                lifetime: 0.5..0.5,
+                frames: vec![SheetRegion::new(0, 0, 0, 0, 8, 8)],
+                ..Emitter::default()
+            })
+            .unwrap();
+        let long = pool
+            .add_emitter(Emitter {
+                lifetime: 2.0..2.0,
+                frames: vec![SheetRegion::new(0, 0, 0, 0, 8, 8)],
+                ..Emitter::default()
+            })
+            .unwrap();
+        pool.spawn(short.0, 2);
+        pool.spawn(long.0, 5);
+        assert_eq!(pool.particles.len(), 4);
+        // the short-lived particles die, leaving room for two more
+        pool.update(0.5);
+        assert_eq!(pool.particles.len(), 2);
+        pool.spawn(long.0, 5);
+        assert_eq!(pool.particles.len(), 4);
+        assert!(pool.particles.iter().all(|p| p.emitter == long.0));
+        pool.clear();
+        assert!(pool.particles.is_empty());
+    }
+
+    #[test]
+    fn frames_spread_over_a_life_or_loop_at_their_fps() {
+        let mut emitter = Emitter {
+            frames: (0..3).map(|i| SheetRegion::new(0, i * 8, 0, 0, 8, 8)).collect(),
+            ..Emitter::default()
+        };
+        let frame_x = |emitter: &Emitter, age, t| emitter.frame_at(age, t).x;
+        assert_eq!(frame_x(&emitter, 0.0, 0.0), 0);
+        assert_eq!(frame_x(&emitter, 0.0, 0.5), 8);
+        assert_eq!(frame_x(&emitter, 0.0, 0.9), 16);
+        assert_eq!(frame_x(&emitter, 0.0, 1.0), 16);
+        emitter.fps = 2.0;
+        assert_eq!(frame_x(&emitter, 0.4, 0.0), 0);
+        assert_eq!(frame_x(&emitter, 0.6, 0.0), 8);
+        assert_eq!(frame_x(&emitter, 1.6, 0.0), 0);
+    }
+
+    #[test]
+    fn colors_and_sizes_blend_between_their_keys() {
+        let emitter = Emitter {
+            colors: vec![
+                Color::rgba(0, 0, 0, 0),
+                Color::rgba(200, 100, 50, 255),
+                Color::rgba(0, 0, 0, 255),
+            ],
+            sizes: vec![Vec2::ZERO, Vec2::new(2.0, 4.0)],
+            ..Emitter::default()
+        };
+        assert_eq!(emitter.color_at(0.0), Color::rgba(0, 0, 0, 0));
+        assert_eq!(emitter.color_at(0.25), Color::rgba(100, 50, 25, 128));
+        assert_eq!(emitter.color_at(0.5), Color::rgba(200, 100, 50, 255));
+        assert_eq!(emitter.color_at(1.0), Color::rgba(0, 0, 0, 255));
+        assert_eq!(emitter.size_at(0.5), Vec2::new(1.0, 2.0));
+        assert_eq!(emitter.size_at(2.0), Vec2::new(2.0, 4.0));
+        // a single key never changes
+        let steady = Emitter::default();
+        assert_eq!(steady.color_at(0.7), Color::WHITE);
+        assert_eq!(steady.size_at(0.7), Vec2::ONE);
+    }
+
+    #[test]
+    fn particles_are_sorted_back_to_front_keeping_their_order_within_a_depth() {
+        let mut pool = Pool::new(8);
+        let near = pool
+            .add_emitter(Emitter {
+                frames: vec![SheetRegion::new(0, 0, 0, 1, 8, 8)],
+                ..Emitter::default()
+            })
+            .unwrap();
+        let far = pool
+            .add_emitter(Emitter {
+                frames: vec![SheetRegion::new(0, 0, 0, 3, 8, 8)],
+                ..Emitter::default()
+            })
+            .unwrap();
+        pool.spawn(near.0, 1);
+        pool.spawn(far.0, 1);
+        pool.update(0.25);
+        pool.spawn(near.0, 1);
+        pool.sort_back_to_front();
+        let order: Vec<_> = pool.particles.iter().map(|p| (p.emitter, p.age)).collect();
+        assert_eq!(order, [(far.0, 0.25), (near.0, 0.25), (near.0, 0.0)]);
+    }
+
+    #[test]
+    fn emitters_missing_frames_colors_or_sizes_are_turned_away() {
+        let mut pool = Pool::new(8);
+        assert_eq!(pool.add_emitter(Emitter::default()), Err(EmitterError::NoFrames));
+        let frames = vec![SheetRegion::new(0, 0, 0, 0, 8, 8)];
+        let no_colors = Emitter {
+            colors: vec![],
+            frames: frames.clone(),
+            ..Emitter::default()
+        };
+        assert_eq!(pool.add_emitter(no_colors), Err(EmitterError::NoColors));
+        let no_sizes = Emitter {
+            sizes: vec![],
+            frames,
+            ..Emitter::default()
+        };
+        assert_eq!(pool.add_emitter(no_sizes), Err(EmitterError::NoSizes));
+        assert!(pool.emitters.is_empty());
+    }
+}
@@ -2,9 +2,11 @@
 
 use engine::wgpu;
 use engine::animation::Animation;
+use engine::atlas::{Atlas, AtlasError};
 use engine::gamestate::GameState;
+use engine::particles::{Emitter, EmitterId, EmitterShape, ParticleSystem};
 // use engine::action::Action;
-use engine::{geom::*, Camera, Engine, SheetRegion, Transform, Zeroable};
+use engine::{geom::*, Camera, Color, Engine, PostEffect, Presentation, SheetRegion, SpriteGroupId, Transform, Transition, Zeroable};
 use rand::Rng;
 use std::time::{Duration, Instant};
 const world_W: f32 = 320.0;
@@ -33,16 +35,157 @@ struct Log {
 struct Bear {
     pos: Vec2,
     bear_count: u32,
+    facing_left: bool,
 }
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq)]
 enum FireSize {
     Small = 1,
     Medium = 2,
     Large = 3,
 }
 
+// handles to the sprite groups made in Game::new
+#[derive(Clone, Copy)]
+struct SpriteGroups {
+    bg: SpriteGroupId,
+    sprites: SpriteGroupId,
+    title: SpriteGroupId,
+    bear_attack: SpriteGroupId,
+    instructions: SpriteGroupId,
+    win: SpriteGroupId,
+    lose: SpriteGroupId,
+}
+
+// sprite regions looked up by name in content/spritesheet.json
+struct Sheets {
+    // 0: front, 1: back, 2: left, 3: right
+    man: [SheetRegion; 4],
+    log: SheetRegion,
+    tree: SheetRegion,
+    campsite: SheetRegion,
+    firepit: SheetRegion,
+    // flame animation frames
+    fire: Vec<SheetRegion>,
+}
+
+impl Sheets {
+    fn new(atlas: &Atlas) -> Result<Self, AtlasError> {
+        Ok(Sheets {
+            man: [
+                atlas.region("manFront")?.depth(3),
+                atlas.region("manBack")?.depth(3),
+                atlas.region("manLeft")?.depth(3),
+                atlas.region("manRight")?.depth(3),
+            ],
+            log: atlas.region("log")?.depth(2),
+            tree: atlas.region("tree")?.depth(4),
+            campsite: atlas.region("house")?.depth(2),
+            firepit: atlas.region("grassDirt")?.depth(4),
+            fire: atlas
+                .regions(["fire1", "fire2", "fire3", "fire4"])?
+                .into_iter()
+                .map(|frame| frame.depth(1))
+                .collect(),
+        })
+    }
+}
+
+// the campfire is drawn with particles: flames, smoke, and sparks
+struct Campfire {
+    particles: ParticleSystem,
+    flames: EmitterId,
+    smoke: EmitterId,
+    sparks: EmitterId,
+}
+
+impl Campfire {
+    fn new(engine: &mut Engine, tex: &wgpu::Texture, frames: &[SheetRegion], camera: Camera) -> Self {
+        let mut particles = ParticleSystem::new(&mut engine.renderer, tex, 512, camera);
+        let flames = particles
+            .add_emitter(Emitter {
+                position: FIREPIT_POS,
+                lifetime: 0.5..0.9,
+                speed: 1.5..3.0,
+                spread: 0.3,
+                gravity: Vec2 { x: 0.0, y: 2.0 },
+                // fade in, then burn from yellow to red and out
+                colors: vec![
+                    Color::rgba(255, 240, 200, 0),
+                    Color::rgba(255, 200, 120, 230),
+                    Color::rgba(255, 90, 30, 0),
+                ],
+                frames: frames.to_vec(),
+                fps: 8.0,
+                ..Emitter::default()
+            })
+            .unwrap();
+        // smoke is the flame frames tinted grey, drifting up behind the fire
+        let smoke = particles
+            .add_emitter(Emitter {
+                position: FIREPIT_POS + Vec2 { x: 0.0, y: 1.0 },
+                lifetime: 1.5..2.5,
+                speed: 1.0..2.0,
+                spread: 0.4,
+                gravity: Vec2 { x: 0.4, y: 0.5 },
+                colors: vec![
+                    Color::rgba(90, 90, 90, 0),
+                    Color::rgba(90, 90, 90, 110),
+                    Color::rgba(120, 120, 120, 0),
+                ],
+                frames: frames.iter().map(|frame| frame.depth(2)).collect(),
+                fps: 4.0,
+                ..Emitter::default()
+            })
+            .unwrap();
+        let sparks = particles
+            .add_emitter(Emitter {
+                position: FIREPIT_POS,
+                lifetime: 0.6..1.2,
+                speed: 4.0..8.0,
+                spread: 0.5,
+                gravity: Vec2 { x: 0.0, y: -6.0 },
+                colors: vec![Color::rgb(255, 230, 120), Color::rgba(255, 120, 40, 0)],
+                sizes: vec![Vec2 { x: 0.5, y: 0.15 }],
+                frames: frames[..1].to_vec(),
+                align_to_velocity: true,
+                ..Emitter::default()
+            })
+            .unwrap();
+        Campfire { particles, flames, smoke, sparks }
+    }
+
+    // size 0 puts the fire out, 3 is the biggest fire
+    fn set_size(&mut self, size: f32) {
+        let burning = size > 0.0;
+        let flames = Emitter {
+            active: burning,
+            rate: 20.0 * size,
+            shape: EmitterShape::Circle { radius: 0.6 * size },
+            sizes: vec![Vec2::splat(2.0 * size), Vec2::splat(0.8 * size)],
+            ..self.particles.emitter(self.flames).clone()
+        };
+        self.particles.set_emitter(self.flames, flames).unwrap();
+        let smoke = Emitter {
+            active: burning,
+            rate: 4.0 * size,
+            shape: EmitterShape::Circle { radius: 0.4 * size },
+            sizes: vec![Vec2::splat(1.0 * size), Vec2::splat(3.0 * size)],
+            ..self.particles.emitter(self.smoke).clone()
+        };
+        self.particles.set_emitter(self.smoke, smoke).unwrap();
+        let sparks = Emitter {
+            active: burning,
+            rate: 2.0 * size,
+            ..self.particles.emitter(self.sparks).clone()
+        };
+        self.particles.set_emitter(self.sparks, sparks).unwrap();
+    }
+}
+
 struct Game {
+    groups: SpriteGroups,
+    sheets: Sheets,
     camera: engine::Camera,
     trees: Vec<AABB>,
     guy: Guy,
@@ -55,6 +198,7 @@ struct Game {
     state: GameState,
     has_fire: bool,
     fire_size: FireSize,
+    campfire: Campfire,
     friction_count: u32,
     fire_timer: Option<Instant>,
 }
@@ -72,10 +216,12 @@ fn new_log_pos(trees: &Vec<AABB>) -> Vec2 {
 
 impl engine::Game for Game {
     fn new(engine: &mut Engine) -> Self {
-        let camera = Camera {
-            screen_pos: [0.0, 0.0],
-            screen_size: [W, H],
-        };
+        let camera = Camera::new([0.0, 0.0], [W, H]);
+        // only the ratio matters, so scale the world size up to whole pixels
+        engine.renderer.set_presentation(Presentation::Letterbox {
+            width: (W * 10.0) as u32,
+            height: (H * 10.0) as u32,
+        });
         #[cfg(target_arch = "wasm32")]
         let sprite_img = {
             let img_bytes = include_bytes!("content/demo.png");
@@ -86,19 +232,19 @@ impl engine::Game for Game {
         };
 
         #[cfg(not(target_arch = "wasm32"))]
-        // SPRITE GROUPS: 0: bg, 1: sprites
-        // 2: bgTitle, 3: bgBearAttack, 4: bgInstructions, 5: Win, 6: Lose
+        // SPRITE GROUPS: 0: bg, 1: sprites, 2: campfire particles
+        // 3: bgTitle, 4: bgBearAttack, 5: bgInstructions, 6: Win, 7: Lose
 
         // add background group
         let background_img = image::open("content/background_grass.jpeg").unwrap().into_rgba8();
-        let background_tex = engine.renderer.gpu.create_texture(
+        let background_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             //createarraytexture
             &background_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let bg = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_tex,
             vec![Transform::zeroed(); 1],
@@ -108,29 +254,34 @@ impl engine::Game for Game {
 
         // add man group
         let sprite_img = image::open("content/spritesheet.png").unwrap().into_rgba8();
+        let atlas = Atlas::load("content/spritesheet.json").unwrap();
+        let sheets = Sheets::new(&atlas).unwrap();
         let sprite_tex = engine.renderer.gpu.create_texture(
             &sprite_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             sprite_img.dimensions(),
             Some("spr-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let sprites = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &sprite_tex,
-            vec![Transform::zeroed(); 40], 
-            vec![SheetRegion::zeroed(); 40], // man (0), bears (1-4), logs (5-20), trees (21-36), campsite (37), firepit (38), fire (39)
+            vec![Transform::zeroed(); 39], 
+            vec![SheetRegion::zeroed(); 39], // man (0), bears (1-4), logs (5-20), trees (21-36), campsite (37), firepit (38)
             camera,
         );
+        // the fire and smoke fade in and out, so they get their own small blended group
+        // in the campfire's particle system; everything else stays cutout
+        let campfire = Campfire::new(engine, &sprite_tex, &sheets.fire, camera);
 
         // add Title group
         let background_title_img = image::open("content/bgTitle.png").unwrap().into_rgba8();
-        let background_title_tex = engine.renderer.gpu.create_texture(
+        let background_title_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_title_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_title_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let title = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_title_tex,
             vec![Transform::zeroed(); 1],
@@ -140,13 +291,13 @@ impl engine::Game for Game {
 
         // add End Game Bear Attack group
         let background_bear_attack_img = image::open("content/bgBearAttack.png").unwrap().into_rgba8();
-        let background_bear_attack_tex = engine.renderer.gpu.create_texture(
+        let background_bear_attack_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_bear_attack_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_bear_attack_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let bear_attack = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_bear_attack_tex,
             vec![Transform::zeroed(); 1],
@@ -156,13 +307,13 @@ impl engine::Game for Game {
 
         // add Instructions group
         let background_instructions_img = image::open("content/campingInstructions.png").unwrap().into_rgba8();
-        let background_instructions_tex = engine.renderer.gpu.create_texture(
+        let background_instructions_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_instructions_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_instructions_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let instructions = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_instructions_tex,
             vec![Transform::zeroed(); 1],
@@ -172,13 +323,13 @@ impl engine::Game for Game {
 
         // add Win group
         let background_instructions_img = image::open("content/winFire.png").unwrap().into_rgba8();
-        let background_instructions_tex = engine.renderer.gpu.create_texture(
+        let background_instructions_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_instructions_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_instructions_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let win = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_instructions_tex,
             vec![Transform::zeroed(); 1],
@@ -188,13 +339,13 @@ impl engine::Game for Game {
 
         // add Lose group
         let background_instructions_img = image::open("content/Lose.jpg").unwrap().into_rgba8();
-        let background_instructions_tex = engine.renderer.gpu.create_texture(
+        let background_instructions_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_instructions_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_instructions_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let lose = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_instructions_tex,
             vec![Transform::zeroed(); 1],
@@ -202,6 +353,16 @@ impl engine::Game for Game {
             camera,
         );
 
+        // the backgrounds are big images shrunk into a small world, so smooth
+        // them with trilinear filtering instead of keeping them pixelated
+        for group in [bg, title, bear_attack, instructions, win, lose] {
+            engine.renderer.sprites.set_sampler(
+                &engine.renderer.gpu,
+                group,
+                &engine::sampler_descriptor(wgpu::FilterMode::Linear, wgpu::AddressMode::ClampToEdge),
+            );
+        }
+
         let guy = Guy {
             pos: Vec2 {
                 x: world_W/2.0,
@@ -223,7 +384,7 @@ impl engine::Game for Game {
         .collect();
 
         let bears: Vec<Bear> = (0..4)
-        .map(|_| Bear {pos: Vec2 {x: rng.gen_range(0.0..world_W), y: rng.gen_range(0.0..world_H)}, bear_count: 0})
+        .map(|_| Bear {pos: Vec2 {x: rng.gen_range(0.0..world_W), y: rng.gen_range(0.0..world_H)}, bear_count: 0, facing_left: false})
         .collect();
 
         // print bears coords on one line
@@ -232,21 +393,19 @@ impl engine::Game for Game {
         }
 
         // Create the bear animation
-        let mut bear_frames: Vec<[f32; 6]> = vec![
+        let mut bear_frames: Vec<SheetRegion> = atlas
             // bear 5 positions
-            [0.0, 973.0, 1.0, 2.0, 64.0, 33.0],
-            [0.0, 1039.0, 1.0, 2.0, 64.0, 33.0],
-            [0.0, 1105.0, 1.0, 2.0, 64.0, 33.0],
-            [0.0, 973.0, 36.0, 2.0, 64.0, 33.0],
-            [0.0, 1039.0, 36.0, 2.0, 64.0, 33.0],
-        ];
+            .regions(["bearWalk1", "bearWalk2", "bearWalk3", "bearWalk4", "bearWalk5"])
+            .expect("missing bear frames in content/spritesheet.json")
+            .into_iter()
+            .map(|frame| frame.depth(2))
+            .collect();
         let mut bear_anim = Animation {
             states: bear_frames,
             frame_counter: 0,
             rate: 40,
             state_number: 0,
             is_facing_left: false,
-            sprite_width: 64.0,
             is_looping: true,
             is_done: false,
         };
@@ -257,7 +416,19 @@ impl engine::Game for Game {
             SheetRegion::new(0, 0, 512, 0, 80, 8),
             10,
         );
+        let groups = SpriteGroups {
+            bg,
+            sprites,
+            title,
+            bear_attack,
+            instructions,
+            win,
+            lose,
+        };
+
         Game {
+            groups,
+            sheets,
             camera,
             guy,
             trees: trees,
@@ -270,15 +441,35 @@ impl engine::Game for Game {
             state: GameState::Title,
             has_fire: false,
             fire_size: FireSize::Medium,
+            campfire,
             friction_count: 0,
             fire_timer: None,
         }
     }
     fn update(&mut self, engine: &mut Engine) {
 
+        // F12 saves a screenshot of the current frame
+        if engine.input.is_key_pressed(winit::event::VirtualKeyCode::F12) {
+            let stamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            let path = format!("screenshot-{}.png", stamp);
+            match engine.renderer.save_screenshot(&path) {
+                Ok(()) => log::info!("saved {}", path),
+                Err(e) => log::warn!("couldn't save screenshot: {}", e),
+            }
+        }
+
         if self.state == GameState::Title{
             if engine.input.is_key_pressed(winit::event::VirtualKeyCode::Space) {
                 self.state = GameState::Instructions;
+                engine.renderer.start_transition(
+                    Transition::Crossfade,
+                    &[self.groups.title],
+                    &[self.groups.instructions],
+                    0.5,
+                );
             }
             return;
         }
@@ -287,6 +478,14 @@ impl engine::Game for Game {
             if engine.input.is_key_pressed(winit::event::VirtualKeyCode::Space) {
                 self.state = GameState::Play;
                 self.start_timer = Some(Instant::now());
+                // the campsite is dark at night, so darken the edges of the screen while playing
+                engine.renderer.set_post_effects(&[PostEffect::vignette(0.85)]);
+                engine.renderer.start_transition(
+                    Transition::Fade { color: [0.0, 0.0, 0.0] },
+                    &[self.groups.instructions],
+                    &[self.groups.bg, self.groups.sprites],
+                    1.0,
+                );
             }
             return;
         }
@@ -437,6 +636,7 @@ impl engine::Game for Game {
                 let xdir = if rng.gen_range(0..2) > 0 {1.0} else {-1.0};
                 let ydir = if rng.gen_range(0..2) > 0 {1.0} else {-1.0};
                 bear.pos.x += xdir * 1.0;
+                bear.facing_left = xdir < 0.0;
                 bear.pos.y += ydir * 1.0;
                 bear.bear_count =0;
             }
@@ -481,6 +681,10 @@ impl engine::Game for Game {
             }
         }
 
+        // the fire burns bigger the more logs are on it
+        self.campfire.set_size(if self.has_fire { self.fire_size as u32 as f32 } else { 0.0 });
+        self.campfire.particles.update(engine::DT);
+
         // check guy collision with log
         if self.guy.log_idx == 0 {
             if let Some(idx) = self
@@ -546,11 +750,13 @@ impl engine::Game for Game {
         // check guy collision with bear
         if self.bears.iter().any(|bear| bear.pos.distance(self.guy.pos) <= BEAR_DISTANCE) {
             self.state = GameState::Attack;
+            engine.renderer.set_post_effects(&[]);
         }
 
         // currently win if have 5 logs and fire
         if self.logs_collected == 5 && self.has_fire{
             self.state = GameState::Win;
+            engine.renderer.set_post_effects(&[]);
         }
 
         // timer for game
@@ -558,6 +764,7 @@ impl engine::Game for Game {
             if new_now.duration_since(start_time) >= Duration::from_secs(TIME_LIMIT) && !self.has_fire{
                 self.state = GameState::Lose;
                 self.start_timer = None;
+                engine.renderer.set_post_effects(&[]);
             }
         }
         
@@ -567,7 +774,7 @@ impl engine::Game for Game {
 
         if self.state == GameState::Title{
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(2);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.title);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: W / 2.0,
@@ -581,23 +788,13 @@ impl engine::Game for Game {
             engine
             .renderer
             .sprites
-            .upload_sprites(&engine.renderer.gpu, 2, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .set_camera(&engine.renderer.gpu, 2, self.camera);
+            .set_camera(&engine.renderer.gpu, self.groups.title, self.camera);
             return;
         }
 
         else if self.state == GameState::Instructions {
-            // remove title bg
-            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(2);
-            trfs[0] = Transform::zeroed();
-            uvs[0] = SheetRegion::zeroed();
-
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(4);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.instructions);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: W / 2.0,
@@ -611,23 +808,13 @@ impl engine::Game for Game {
             engine
             .renderer
             .sprites
-            .upload_sprites(&engine.renderer.gpu, 2, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 4, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .set_camera(&engine.renderer.gpu, 4, self.camera);
+            .set_camera(&engine.renderer.gpu, self.groups.instructions, self.camera);
             return;
         }
 
         else if self.state == GameState::Attack{
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(3);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.bear_attack);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: self.camera.screen_pos[0] + W / 2.0,
@@ -639,31 +826,18 @@ impl engine::Game for Game {
             uvs_bg[0] = SheetRegion::new(0, 0, 0, 1, 533, 400);
 
             // remove bg
-            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(0);
+            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.groups.bg);
             trfs[0] = Transform::zeroed();
             uvs[0] = SheetRegion::zeroed();
 
             // remove all other sprites
-            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(1);
-            for i in 0..40 {
+            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.groups.sprites);
+            for i in 0..39 {
                 trfs[i] = Transform::zeroed();
                 uvs[i] = SheetRegion::zeroed();
             }
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 3, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 0, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 1, 0..40);
+            self.campfire.particles.clear();
+            self.campfire.particles.draw(&mut engine.renderer);
 
             engine
             .renderer
@@ -674,7 +848,7 @@ impl engine::Game for Game {
 
         else if self.state == GameState::Win{
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(5);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.win);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: self.camera.screen_pos[0] + W / 2.0,
@@ -686,31 +860,18 @@ impl engine::Game for Game {
             uvs_bg[0] = SheetRegion::new(0, 0, 0, 1, 533, 400);
 
             // remove bg
-            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(0);
+            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.groups.bg);
             trfs[0] = Transform::zeroed();
             uvs[0] = SheetRegion::zeroed();
 
             // remove all other sprites
-            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(1);
-            for i in 0..40 {
+            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.groups.sprites);
+            for i in 0..39 {
                 trfs[i] = Transform::zeroed();
                 uvs[i] = SheetRegion::zeroed();
             }
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 5, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 0, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 1, 0..40);
+            self.campfire.particles.clear();
+            self.campfire.particles.draw(&mut engine.renderer);
 
             engine
             .renderer
@@ -721,7 +882,7 @@ impl engine::Game for Game {
 
         else if self.state == GameState::Lose{
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(6);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.lose);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: self.camera.screen_pos[0] + W / 2.0,
@@ -733,31 +894,18 @@ impl engine::Game for Game {
             uvs_bg[0] = SheetRegion::new(0, 0, 0, 1, 533, 400);
 
             // remove bg
-            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(0);
+            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.groups.bg);
             trfs[0] = Transform::zeroed();
             uvs[0] = SheetRegion::zeroed();
 
             // remove all other sprites
-            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(1);
-            for i in 0..40 {
+            let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.groups.sprites);
+            for i in 0..39 {
                 trfs[i] = Transform::zeroed();
                 uvs[i] = SheetRegion::zeroed();
             }
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 6, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 0, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 1, 0..40);
+            self.campfire.particles.clear();
+            self.campfire.particles.draw(&mut engine.renderer);
 
             engine
             .renderer
@@ -766,13 +914,8 @@ impl engine::Game for Game {
             return;
         }
 
-        // remove instructions bg
-        let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(4);
-        trfs[0] = Transform::zeroed();
-        uvs[0] = SheetRegion::zeroed();
-
         // set bg image
-        let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(0);
+        let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.bg);
         trfs_bg[0] = AABB {
             center: Vec2 {
                 x: world_W / 2.0,
@@ -784,13 +927,10 @@ impl engine::Game for Game {
         uvs_bg[0] = SheetRegion::new(0, 0, 0, 6, 1920, 1280);
 
         // set sprites
-        let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(1);
+        let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.groups.sprites);
 
         // 0: front, 1: back, 2: left, 3: right
-        let front_sheet = SheetRegion::new(0, 1363, 227, 3, 166, 232);
-        let back_sheet = SheetRegion::new(0, 1187, 227, 3, 174, 228);
-        let left_sheet = SheetRegion::new(0, 1531, 227, 3, 156, 238);
-        let right_sheet = SheetRegion::new(0, 1689, 227, 3, 154, 234);
+        let [front_sheet, back_sheet, left_sheet, right_sheet] = self.sheets.man;
         // set guy
         trfs[0] = AABB {
             center: self.guy.pos,
@@ -810,7 +950,7 @@ impl engine::Game for Game {
             uvs[0] = right_sheet;
         }
         
-        // SPRITE INDICES: man (0), bears (1-4), logs (5-20), trees (21-36), campsite (37), firepit (38), fire (39)
+        // SPRITE INDICES: man (0), bears (1-4), logs (5-20), trees (21-36), campsite (37), firepit (38)
 
         // set bears
         for i in 1..5 {
@@ -823,14 +963,7 @@ impl engine::Game for Game {
                 size: Vec2 { x: 16.0, y: 8.75 },
             }
             .into();
-            uvs[i] = SheetRegion::new(
-                current_state[0] as u16,
-                current_state[1] as u16,
-                current_state[2] as u16,
-                current_state[3] as u16,
-                current_state[4] as u16,
-                current_state[5] as u16,
-            );
+            uvs[i] = current_state.flip_x(self.bears[i - 1].facing_left);
 
             // Tick the animation for the next frame
             self.bear_anim.tick();
@@ -842,7 +975,7 @@ impl engine::Game for Game {
                 center: self.logs[i-5].pos,
                 size: Vec2 { x: 6.0, y: 2.0 },
             }.into();
-            uvs[i] = SheetRegion::new(0, 1171, 1, 2, 672, 224);
+            uvs[i] = self.sheets.log;
         }
 
         // set trees
@@ -851,7 +984,7 @@ impl engine::Game for Game {
                 center: self.trees[i-21].center,
                 size: Vec2 { x: 11.0, y: 11.0 },
             }.into();
-            uvs[i] = SheetRegion::new(0, 1187, 463, 4, 294, 294);
+            uvs[i] = self.sheets.tree;
         }
 
         // set campsite
@@ -862,32 +995,17 @@ impl engine::Game for Game {
             },
             size: Vec2 { x: 10.0, y: 13.6 },
         }.into();
-        uvs[37] = SheetRegion::new(0, 769, 759, 2, 230, 314);
+        uvs[37] = self.sheets.campsite;
 
         // set firepit
         trfs[38] = AABB {
             center: FIREPIT_POS,
             size: Vec2 { x: 10.0, y: 10.0},
         }.into();
-        uvs[38] = SheetRegion::new(0, 1, 759, 4, 322, 322);
-
-        // add fire with appropriate size
-        let mut fire_size =  Vec2 { x: 0.0, y: 0.0 };
-        if self.has_fire { 
-            match self.fire_size {
-                FireSize::Small => fire_size = Vec2 { x: 3.0, y: 3.1 },
-                FireSize::Medium => fire_size = Vec2 { x: 6.0, y: 6.1 },
-                FireSize::Large => fire_size = Vec2 { x: 10.0, y: 10.2 },
-            }
-        }
-        trfs[39] = AABB {
-            center: Vec2 {
-                x: FIREPIT_POS.x,
-                y: FIREPIT_POS.y,
-            },
-            size:  fire_size
-        }.into();
-        uvs[39] = SheetRegion::new(0, 811, 141, 1, 286, 292);
+        uvs[38] = self.sheets.firepit;
+
+        // add fire
+        self.campfire.particles.draw(&mut engine.renderer);
 
         // let score_str = self.score.to_string();
         // let text_len = score_str.len();
@@ -910,19 +1028,6 @@ impl engine::Game for Game {
         //     .into(),
         //     16.0,
         // );
-
-        engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 0, 0..1);
-        engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 1, 0..40);
-        engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 4, 0..1);
         // engine
         //     .renderer
         //     .sprites
@@ -931,10 +1036,13 @@ impl engine::Game for Game {
         (self.guy.pos.x - (W / 2.0)).max(0.0).min(world_W - self.camera.screen_size[0]),
         (self.guy.pos.y - (H / 2.0)).max(0.0).min(world_H - self.camera.screen_size[1]),
         ];
-        engine
-            .renderer
-            .sprites
-            .set_camera_all(&engine.renderer.gpu, self.camera)
+        // leave the instructions where they are while they fade out
+        for group in [self.groups.bg, self.groups.sprites, self.campfire.particles.group()] {
+            engine
+                .renderer
+                .sprites
+                .set_camera(&engine.renderer.gpu, group, self.camera);
+        }
     }
 }
 fn main() {
@@ -1,8 +1,10 @@
 // TODO: use AABB instead of Rect for centered box, so collision checking doesn't have to offset by half size
 
 use engine::wgpu;
+use engine::atlas::{Atlas, AtlasError};
 use engine::gamestate::GameState;
-use engine::{geom::*, Camera, Engine, SheetRegion, Transform, Zeroable};
+use engine::particles::{Emitter, EmitterId, EmitterShape, ParticleSystem};
+use engine::{geom::*, Camera, Color, Engine, Presentation, SheetRegion, SpriteGroupId, TilemapId, Tileset, Transform, Transition, WipeDirection, Zeroable};
 use rand::Rng;
 use std::time::{Duration, Instant};
 use std::usize;
@@ -10,10 +12,15 @@ const world_W: f32 = 320.0;
 const world_H: f32 = 240.0;
 const W: f32 = 240.0;
 const H: f32 = 120.0;
+// the floor picture is cut into this many tiles across and down
+const FLOOR_COLUMNS: u32 = 6;
+const FLOOR_ROWS: u32 = 4;
 const GUY_SPEED: f32 = 0.75;
 const CATCH_DISTANCE: f32 = 9.0;
 const TIME_LIMIT: u64 = 45;
 const REMEMBER_TIME_LIMIT: u64 = 4;
+// seconds the title screen takes to fade in
+const TITLE_FADE_IN: f32 = 1.5;
 
 struct Guy {
     pos: Vec2,
@@ -34,10 +41,62 @@ struct Spellbook {
     color: usize,
 }
 
+// handles to the sprite groups made in Game::new
+#[derive(Clone, Copy)]
+struct SpriteGroups {
+    sprites: SpriteGroupId,
+    title: SpriteGroupId,
+    wrong: SpriteGroupId,
+    instructions: SpriteGroupId,
+    win: SpriteGroupId,
+    lose: SpriteGroupId,
+    text: SpriteGroupId,
+}
+
+// sprite regions looked up by name in content-2/spritesheet.json
+struct Sheets {
+    // 0: blue, 1: purple, 2: green, 3: red, 4: yellow
+    potions: [SheetRegion; 5],
+    // 0: front, 1: back, 2: left, 3: right
+    girl: [SheetRegion; 4],
+    // 0: good, 1: death
+    books: [SheetRegion; 2],
+}
+
+impl Sheets {
+    fn new(atlas: &Atlas) -> Result<Self, AtlasError> {
+        Ok(Sheets {
+            potions: [
+                atlas.region("blue")?.depth(2),
+                atlas.region("purple")?.depth(2),
+                atlas.region("green")?.depth(2),
+                atlas.region("red")?.depth(2),
+                atlas.region("yellow")?.depth(2),
+            ],
+            girl: [
+                atlas.region("frontgirl")?.depth(3),
+                atlas.region("backgirl")?.depth(3),
+                atlas.region("leftgirl")?.depth(3),
+                atlas.region("rightgirl")?.depth(3),
+            ],
+            books: [
+                atlas.region("bluebook")?.depth(4),
+                atlas.region("deathbook")?.depth(4),
+            ],
+        })
+    }
+}
+
 struct Game {
+    groups: SpriteGroups,
+    floor: TilemapId,
+    sheets: Sheets,
     camera: engine::Camera,
     guy: Guy,
     potions: Vec<Potion>,
+    // bursts of sparkles when a potion is collected, one emitter per potion color
+    sparkles: ParticleSystem,
+    potion_bursts: Vec<EmitterId>,
     level_potions: Vec<i32>,
     books: Vec<Spellbook>,
     level_timer: Option<Instant>,
@@ -48,6 +107,7 @@ struct Game {
     font: engine::BitFont,
     state: GameState,
     life: u32,
+    title_shown: Instant,
 }
 
 // function creates a new random position
@@ -64,10 +124,11 @@ fn new_random_pos(potions: &Vec<Potion>) -> Vec2 {
 
 impl engine::Game for Game {
     fn new(engine: &mut Engine) -> Self {
-        let camera = Camera {
-            screen_pos: [0.0, 0.0],
-            screen_size: [W, H],
-        };
+        let camera = Camera::new([0.0, 0.0], [W, H]);
+        engine.renderer.set_presentation(Presentation::Letterbox {
+            width: W as u32,
+            height: H as u32,
+        });
         #[cfg(target_arch = "wasm32")]
         let sprite_img = {
             let img_bytes = include_bytes!("content/demo.png");
@@ -78,34 +139,42 @@ impl engine::Game for Game {
         };
 
         #[cfg(not(target_arch = "wasm32"))]
-        // SPRITE GROUPS: 0: bg, 1: sprites
-        // 2: bgTitle, 3: bgBearAttack, 4: bgInstructions, 5: Win, 6: Lose, 7: font
-
-        // add background group
-        let background_img = image::open("content-2/tile_floor.jpeg").unwrap().into_rgba8();
-        let background_tex = engine.renderer.gpu.create_texture(
-            &background_img,
+        // SPRITE GROUPS: 0: sprites
+        // 1: bgTitle, 2: bgWrong, 3: bgInstructions, 4: Win, 5: Lose, 6: font
+
+        // add floor tilemap, cutting the floor picture into a 6x4 tileset
+        // and laying its tiles back out in order so the picture covers the world
+        let floor_img = image::open("content-2/tile_floor.jpeg").unwrap().into_rgba8();
+        let floor_tex = engine.renderer.gpu.create_texture_with_mipmaps(
+            &floor_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
-            background_img.dimensions(),
-            Some("background-demo.png"),
+            floor_img.dimensions(),
+            Some("tile_floor.jpeg"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let floor_tiles = Tileset::with_sheet_region(SheetRegion::new(0, 0, 0, 6, 624, 416), [104, 104]);
+        let floor = engine.renderer.tilemaps.add_tilemap(
             &engine.renderer.gpu,
-            &background_tex,
-            vec![Transform::zeroed(); 1],
-            vec![SheetRegion::zeroed(); 1],
+            &floor_tex,
+            floor_tiles,
+            FLOOR_COLUMNS,
+            (0..floor_tiles.tile_count() as u16).collect(),
             camera,
         );
+        engine.renderer.tilemaps.set_tile_size(&engine.renderer.gpu, floor, [world_W / FLOOR_COLUMNS as f32, world_H / FLOOR_ROWS as f32]);
+        // the floor only shows up once a level starts
+        engine.renderer.tilemaps.set_visible(floor, false);
 
         // add man group
         let sprite_img = image::open("content-2/spritesheet.png").unwrap().into_rgba8();
+        let atlas = Atlas::load("content-2/spritesheet.json").unwrap();
+        let sheets = Sheets::new(&atlas).unwrap();
         let sprite_tex = engine.renderer.gpu.create_texture(
             &sprite_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             sprite_img.dimensions(),
             Some("spr-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let sprites = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &sprite_tex,
             vec![Transform::zeroed(); 15], 
@@ -113,31 +182,58 @@ impl engine::Game for Game {
             camera,
         );
 
+        // collected potions burst into tiny copies of themselves that fly apart and fall
+        let mut sparkles = ParticleSystem::new(&mut engine.renderer, &sprite_tex, 256, camera);
+        let potion_bursts = sheets
+            .potions
+            .iter()
+            .map(|&potion| {
+                sparkles.add_emitter(Emitter {
+                    shape: EmitterShape::Circle { radius: 2.0 },
+                    lifetime: 0.4..0.8,
+                    speed: 8.0..16.0,
+                    spread: std::f32::consts::PI,
+                    gravity: Vec2 { x: 0.0, y: -20.0 },
+                    colors: vec![Color::WHITE, Color::WHITE.with_alpha(0)],
+                    sizes: vec![Vec2 { x: 2.4, y: 3.0 }, Vec2::ZERO],
+                    frames: vec![potion.depth(1)],
+                    ..Emitter::default()
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
         // add Title group
         let background_title_img = image::open("content-2/bgTitle.png").unwrap().into_rgba8();
-        let background_title_tex = engine.renderer.gpu.create_texture(
+        let background_title_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_title_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_title_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let title = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_title_tex,
             vec![Transform::zeroed(); 1],
             vec![SheetRegion::zeroed(); 1],
             camera,
         );
+        // the title fades in, so blend it with what's behind it
+        engine.renderer.sprites.set_blend_mode(
+            &engine.renderer.gpu,
+            title,
+            engine::BlendMode::Alpha,
+        );
 
         // add End Game wrong sequence
         let background_bear_attack_img = image::open("content-2/wrong.png").unwrap().into_rgba8();
-        let background_bear_attack_tex = engine.renderer.gpu.create_texture(
+        let background_bear_attack_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_bear_attack_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_bear_attack_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let wrong = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_bear_attack_tex,
             vec![Transform::zeroed(); 1],
@@ -147,13 +243,13 @@ impl engine::Game for Game {
 
         // add Instructions group
         let background_instructions_img = image::open("content-2/instruct.png").unwrap().into_rgba8();
-        let background_instructions_tex = engine.renderer.gpu.create_texture(
+        let background_instructions_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_instructions_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_instructions_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let instructions = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_instructions_tex,
             vec![Transform::zeroed(); 1],
@@ -163,13 +259,13 @@ impl engine::Game for Game {
 
         // add Win group
         let background_instructions_img = image::open("content-2/win.png").unwrap().into_rgba8();
-        let background_instructions_tex = engine.renderer.gpu.create_texture(
+        let background_instructions_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_instructions_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_instructions_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let win = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_instructions_tex,
             vec![Transform::zeroed(); 1],
@@ -179,13 +275,13 @@ impl engine::Game for Game {
 
         // add Lose group when time runs out
         let background_instructions_img = image::open("content-2/time_up.png").unwrap().into_rgba8();
-        let background_instructions_tex = engine.renderer.gpu.create_texture(
+        let background_instructions_tex = engine.renderer.gpu.create_texture_with_mipmaps(
             &background_instructions_img,
             wgpu::TextureFormat::Rgba8UnormSrgb,
             background_instructions_img.dimensions(),
             Some("background-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let lose = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &background_instructions_tex,
             vec![Transform::zeroed(); 1],
@@ -201,7 +297,7 @@ impl engine::Game for Game {
             font_img.dimensions(),
             Some("font.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let text = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &font_tex,
             vec![Transform::zeroed(); 2],
@@ -209,6 +305,21 @@ impl engine::Game for Game {
             camera,
         );
 
+        // the backgrounds are big images shrunk into a small world, so smooth
+        // them with trilinear filtering instead of keeping them pixelated
+        for group in [title, wrong, instructions, win, lose] {
+            engine.renderer.sprites.set_sampler(
+                &engine.renderer.gpu,
+                group,
+                &engine::sampler_descriptor(wgpu::FilterMode::Linear, wgpu::AddressMode::ClampToEdge),
+            );
+        }
+        engine.renderer.tilemaps.set_sampler(
+            &engine.renderer.gpu,
+            floor,
+            &engine::sampler_descriptor(wgpu::FilterMode::Linear, wgpu::AddressMode::ClampToEdge),
+        );
+
         let guy = Guy {
             pos: Vec2 {
                 x: world_W/2.0,
@@ -230,10 +341,25 @@ impl engine::Game for Game {
             10,
         );
 
+        let groups = SpriteGroups {
+            sprites,
+            title,
+            wrong,
+            instructions,
+            win,
+            lose,
+            text,
+        };
+
         Game {
+            groups,
+            floor,
+            sheets,
             camera,
             guy,
             potions: Vec::new(),
+            sparkles,
+            potion_bursts,
             level_potions: level_potions,
             books: Vec::new(),
             level_timer: None,
@@ -244,6 +370,7 @@ impl engine::Game for Game {
             font: font,
             state: GameState::Title,
             life: 1,
+            title_shown: Instant::now(),
         }
     }
     fn update(&mut self, engine: &mut Engine) {
@@ -251,6 +378,12 @@ impl engine::Game for Game {
         if self.state == GameState::Title{
             if engine.input.is_key_pressed(winit::event::VirtualKeyCode::Space) {
                 self.state = GameState::Instructions;
+                engine.renderer.start_transition(
+                    Transition::Wipe { direction: WipeDirection::Left },
+                    &[self.groups.title],
+                    &[self.groups.instructions],
+                    0.6,
+                );
             }
             return;
         }
@@ -259,6 +392,12 @@ impl engine::Game for Game {
             if engine.input.is_key_pressed(winit::event::VirtualKeyCode::Space) {
                 self.state = GameState::ShowLevel;
                 self.level_timer = Some(Instant::now());
+                engine.renderer.start_transition(
+                    Transition::Fade { color: [0.0, 0.0, 0.0] },
+                    &[self.groups.instructions],
+                    &[self.groups.sprites, self.groups.text],
+                    1.0,
+                );
             }
             return;
         }
@@ -383,11 +522,15 @@ impl engine::Game for Game {
             {
                 self.potions_collected.push(self.potions[idx].color as i32);
                 println!("got potion");
+                let burst = self.potion_bursts[self.potions[idx].color];
+                self.sparkles.move_emitter(burst, self.potions[idx].pos);
+                self.sparkles.burst(burst, 24);
                 // change potion position
                 self.potions[idx].pos = new_random_pos(&self.potions);
             }
 
         }
+        self.sparkles.update(engine::DT);
 
         // win level if same input sequence
         if !self.potions_collected.is_empty() && !self.level_potions.is_empty() {
@@ -488,7 +631,7 @@ impl engine::Game for Game {
 
         if self.state == GameState::Title{
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(2);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.title);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: W / 2.0,
@@ -498,25 +641,19 @@ impl engine::Game for Game {
             }
             .into();
             uvs_bg[0] = SheetRegion::new(0, 0, 0, 1, 626, 416);
+            let fade = self.title_shown.elapsed().as_secs_f32() / TITLE_FADE_IN;
+            engine.renderer.sprites.get_colors_mut(self.groups.title)[0] = Color::WHITE.fade(fade);
 
             engine
             .renderer
             .sprites
-            .upload_sprites(&engine.renderer.gpu, 2, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .set_camera(&engine.renderer.gpu, 2, self.camera);
+            .set_camera(&engine.renderer.gpu, self.groups.title, self.camera);
             return;
         }
 
         else if self.state == GameState::Instructions {
-            // remove title bg
-            remove_background(2, engine);
-
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(4);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.instructions);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: W / 2.0,
@@ -530,18 +667,13 @@ impl engine::Game for Game {
             engine
             .renderer
             .sprites
-            .upload_sprites(&engine.renderer.gpu, 4, 0..1);
-
-            engine
-            .renderer
-            .sprites
-            .set_camera(&engine.renderer.gpu, 4, self.camera);
+            .set_camera(&engine.renderer.gpu, self.groups.instructions, self.camera);
             return;
         }
 
         else if self.state == GameState::Attack {
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(3);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.wrong);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: self.camera.screen_pos[0] + W / 2.0,
@@ -552,16 +684,11 @@ impl engine::Game for Game {
             .into();
             uvs_bg[0] = SheetRegion::new(0, 0, 0, 1, 626, 416);
 
-            // remove bg
-            remove_background(0, engine);
+            // remove floor
+            engine.renderer.tilemaps.set_visible(self.floor, false);
 
             // remove all other sprites
-            clear_sprites(engine);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 3, 0..1);
+            clear_sprites(&self.groups, &mut self.sparkles, engine);
 
             engine
             .renderer
@@ -572,7 +699,7 @@ impl engine::Game for Game {
 
         else if self.state == GameState::Win{
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(5);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.win);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: self.camera.screen_pos[0] + W / 2.0,
@@ -583,16 +710,11 @@ impl engine::Game for Game {
             .into();
             uvs_bg[0] = SheetRegion::new(0, 0, 0, 1, 626, 416);
 
-            // remove bg
-            remove_background(0, engine);
+            // remove floor
+            engine.renderer.tilemaps.set_visible(self.floor, false);
 
             // remove all other sprites
-            clear_sprites(engine);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 5, 0..1);
+            clear_sprites(&self.groups, &mut self.sparkles, engine);
 
             engine
             .renderer
@@ -603,7 +725,7 @@ impl engine::Game for Game {
 
         else if self.state == GameState::Lose{
             // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(6);
+            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(self.groups.lose);
             trfs_bg[0] = AABB {
                 center: Vec2 {
                     x: self.camera.screen_pos[0] + W / 2.0,
@@ -614,16 +736,11 @@ impl engine::Game for Game {
             .into();
             uvs_bg[0] = SheetRegion::new(0, 0, 0, 1, 626, 416);
 
-            // remove bg
-            remove_background(0, engine);
+            // remove floor
+            engine.renderer.tilemaps.set_visible(self.floor, false);
 
             // remove all other sprites
-            clear_sprites(engine);
-
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 6, 0..1);
+            clear_sprites(&self.groups, &mut self.sparkles, engine);
 
             engine
             .renderer
@@ -632,34 +749,22 @@ impl engine::Game for Game {
             return;
         }  else if self.state == GameState::ShowLevel { 
 
-            // remove instructions bg
-            remove_background(4, engine);
-
             // remove all other sprites
-            clear_sprites(engine);
+            clear_sprites(&self.groups, &mut self.sparkles, engine);
 
-            // set bg image
-            let (trfs_bg, uvs_bg) = engine.renderer.sprites.get_sprites_mut(0);
-            trfs_bg[0] = AABB {
-                center: Vec2 {
-                    x: world_W / 2.0,
-                    y: world_H / 2.0,
-                },
-                size: Vec2 { x: world_W, y: world_H },
-            }
-            .into();
-            uvs_bg[0] = SheetRegion::new(0, 0, 0, 6, 626, 416);
+            // show floor
+            engine.renderer.tilemaps.set_visible(self.floor, true);
 
             let level_str = self.level.to_string();
             let level_length = level_str.len();
             engine.renderer.sprites.resize_sprite_group(
                 &engine.renderer.gpu,
-                7,
+                self.groups.text,
                 level_length,
             );
             self.font.draw_text(
                 &mut engine.renderer.sprites,
-                7,
+                self.groups.text,
                 0,
                 &level_str,
                 Vec2 { // put numbers in corner
@@ -670,20 +775,10 @@ impl engine::Game for Game {
                 16.0,
             );
 
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 0, 0..1);
-        
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 7, 0..level_length);
-
             // add potion sequence to screen
             //CHANGEEEE
             for i in 0..self.level_potions.len() {
-                let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(1);
+                let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.groups.sprites);
                 trfs[i] = AABB {
                     center: Vec2 {
                         x: self.camera.screen_pos[0] + ((W - 40.0) /(self.level_potions.len()) as f32 * i as f32) + 20.0,
@@ -691,39 +786,17 @@ impl engine::Game for Game {
                     },
                     size: Vec2 { x: 9.6, y: 12.0 },
                 }.into();
-                if self.level_potions[i] == 0 {
-                    uvs[i] = SheetRegion::new(0, 178, 1, 2, 96, 120);
-                }
-                else if self.level_potions[i] == 1 {
-                    uvs[i] = SheetRegion::new(0, 440, 1, 2, 80, 120);
-                }
-                else if self.level_potions[i] == 2 {
-                    uvs[i] = SheetRegion::new(0, 309, 123, 2, 96, 120);
-                }
-                else if self.level_potions[i] == 3 {
-                    uvs[i] = SheetRegion::new(0, 407, 123, 2, 96, 120);
-                }
-                else if self.level_potions[i] == 4 {
-                    uvs[i] = SheetRegion::new(0, 165, 417, 2, 96, 120);
-                }
+                uvs[i] = self.sheets.potions[self.level_potions[i] as usize];
             }
 
-            engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 1, 0..(self.level_potions.len()));
-
             return;
         }
 
         // set sprites
-        let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(1);
+        let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.groups.sprites);
 
         // 0: front, 1: back, 2: left, 3: right
-        let front_sheet = SheetRegion::new(0, 210, 123, 3, 97, 174);
-        let back_sheet = SheetRegion::new(0, 1, 1, 3, 93, 174);
-        let left_sheet = SheetRegion::new(0, 1, 245, 3, 86, 170);
-        let right_sheet = SheetRegion::new(0, 407, 245, 3, 88, 170);
+        let [front_sheet, back_sheet, left_sheet, right_sheet] = self.sheets.girl;
 
         // set guy
         trfs[0] = AABB {
@@ -753,21 +826,7 @@ impl engine::Game for Game {
                 center: self.potions[i-1].pos,
                 size: Vec2 { x: 9.6, y: 12.0 },
             }.into();
-            if self.potions[i-1].color == 0 {
-                uvs[i] = SheetRegion::new(0, 178, 1, 2, 96, 120);
-            }
-            else if self.potions[i-1].color == 1 {
-                uvs[i] = SheetRegion::new(0, 440, 1, 2, 80, 120);
-            }
-            else if self.potions[i-1].color == 2 {
-                uvs[i] = SheetRegion::new(0, 309, 123, 2, 96, 120);
-            }
-            else if self.potions[i-1].color == 3 {
-                uvs[i] = SheetRegion::new(0, 407, 123, 2, 96, 120);
-            }
-            else if self.potions[i-1].color == 4 {
-                uvs[i] = SheetRegion::new(0, 165, 417, 2, 96, 120);
-            }
+            uvs[i] = self.sheets.potions[self.potions[i-1].color];
         }
 
         // set good spellbook
@@ -780,15 +839,13 @@ impl engine::Game for Game {
                     center: self.books[i-11].pos,
                     size: Vec2 { x: 11.2, y: 12.0 },
                 }.into();
-                if self.books[i-11].color == 0 {
-                    uvs[i] = SheetRegion::new(0, 276, 1, 4, 112, 120);
-                }
-                else {
-                    uvs[i] = SheetRegion::new(0, 96, 123, 4, 112, 120);
-                }
+                uvs[i] = self.sheets.books[self.books[i-11].color];
             }
         }
 
+        // set potion sparkles
+        self.sparkles.draw(&mut engine.renderer);
+
         // set timer
         if self.level_timer.unwrap().elapsed().as_secs() >= self.total_time {
             self.state = GameState::Lose;
@@ -799,12 +856,12 @@ impl engine::Game for Game {
         self.timer_length = timer_str.len();
         engine.renderer.sprites.resize_sprite_group(
             &engine.renderer.gpu,
-            7,
+            self.groups.text,
             self.timer_length,
         );
         self.font.draw_text(
             &mut engine.renderer.sprites,
-            7,
+            self.groups.text,
             0,
             &timer_str,
             Vec2 { // put numbers in corner
@@ -814,15 +871,6 @@ impl engine::Game for Game {
             .into(),
             8.0,
         );
-
-        engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 1, 0..15);
-        engine
-            .renderer
-            .sprites
-            .upload_sprites(&engine.renderer.gpu, 7, 0..self.timer_length);
         self.camera.screen_pos = [
         (self.guy.pos.x - (W / 2.0)).max(0.0).min(world_W - self.camera.screen_size[0]),
         (self.guy.pos.y - (H / 2.0)).max(0.0).min(world_H - self.camera.screen_size[1]),
@@ -830,7 +878,11 @@ impl engine::Game for Game {
         engine
             .renderer
             .sprites
-            .set_camera_all(&engine.renderer.gpu, self.camera)
+            .set_camera_all(&engine.renderer.gpu, self.camera);
+        engine
+            .renderer
+            .tilemaps
+            .set_camera(&engine.renderer.gpu, self.floor, self.camera);
     }
 }
 fn main() {
@@ -838,45 +890,24 @@ fn main() {
 }
 
 
-fn clear_sprites(engine: &mut Engine) {
+fn clear_sprites(groups: &SpriteGroups, sparkles: &mut ParticleSystem, engine: &mut Engine) {
 
     // remove all other sprites
-    let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(1);
+    let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(groups.sprites);
     for i in 0..15 {
         trfs[i] = Transform::zeroed();
         uvs[i] = SheetRegion::zeroed();
     }
 
-    engine
-    .renderer
-    .sprites
-    .upload_sprites(&engine.renderer.gpu, 1, 0..15);
-
     // remove all fonts
-    let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(7);
+    let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(groups.text);
     let num_sprites = trfs.len();
     for i in 0..num_sprites {
         trfs[i] = Transform::zeroed();
         uvs[i] = SheetRegion::zeroed();
     }
 
-    engine
-    .renderer
-    .sprites
-    .upload_sprites(&engine.renderer.gpu, 7, 0..num_sprites);
-
-
+    // remove any potion sparkles
+    sparkles.clear();
+    sparkles.draw(&mut engine.renderer);
 }
-
-fn remove_background(background_group: usize, engine: &mut Engine) {
-
-    let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(background_group);
-    trfs[0] = Transform::zeroed();
-    uvs[0] = SheetRegion::zeroed();
-
-    engine
-    .renderer
-    .sprites
-    .upload_sprites(&engine.renderer.gpu, background_group, 0..1);
-
-}
\ No newline at end of file
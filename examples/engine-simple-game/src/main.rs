@@ -2,7 +2,7 @@
 
 use engine_simple as engine;
 use engine_simple::wgpu;
-use engine_simple::{geom::*, Camera, Engine, SheetRegion, Transform, Zeroable};
+use engine_simple::{geom::*, Camera, Engine, SheetRegion, SpriteGroupId, Transform, Zeroable};
 use rand::Rng;
 const W: f32 = 320.0;
 const H: f32 = 240.0;
@@ -20,6 +20,7 @@ struct Apple {
 }
 
 struct Game {
+    sprites: SpriteGroupId,
     camera: engine::Camera,
     walls: Vec<AABB>,
     guy: Guy,
@@ -31,10 +32,7 @@ struct Game {
 
 impl engine::Game for Game {
     fn new(engine: &mut Engine) -> Self {
-        let camera = Camera {
-            screen_pos: [0.0, 0.0],
-            screen_size: [W, H],
-        };
+        let camera = Camera::new([0.0, 0.0], [W, H]);
         #[cfg(target_arch = "wasm32")]
         let sprite_img = {
             let img_bytes = include_bytes!("content/demo.png");
@@ -51,7 +49,7 @@ impl engine::Game for Game {
             sprite_img.dimensions(),
             Some("spr-demo.png"),
         );
-        engine.renderer.sprites.add_sprite_group(
+        let sprites = engine.renderer.sprites.add_sprite_group(
             &engine.renderer.gpu,
             &sprite_tex,
             vec![Transform::zeroed(); SPRITE_MAX], //bg, three walls, guy, a few apples
@@ -86,6 +84,7 @@ impl engine::Game for Game {
             10,
         );
         Game {
+            sprites,
             camera,
             guy,
             walls: vec![left_wall, right_wall, floor],
@@ -182,7 +181,7 @@ impl engine::Game for Game {
     }
     fn render(&mut self, engine: &mut Engine) {
         // set bg image
-        let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(0);
+        let (trfs, uvs) = engine.renderer.sprites.get_sprites_mut(self.sprites);
         trfs[0] = AABB {
             center: Vec2 {
                 x: W / 2.0,
@@ -230,12 +229,12 @@ impl engine::Game for Game {
         let text_len = score_str.len();
         engine.renderer.sprites.resize_sprite_group(
             &engine.renderer.gpu,
-            0,
+            self.sprites,
             sprite_count + text_len,
         );
         self.font.draw_text(
             &mut engine.renderer.sprites,
-            0,
+            self.sprites,
             sprite_count,
             &score_str,
             Vec2 {
@@ -248,7 +247,7 @@ impl engine::Game for Game {
         engine
             .renderer
             .sprites
-            .upload_sprites(&engine.renderer.gpu, 0, 0..sprite_count + text_len);
+            .upload_sprites(&engine.renderer.gpu, self.sprites, 0..sprite_count + text_len);
         engine
             .renderer
             .sprites
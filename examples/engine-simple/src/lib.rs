@@ -1,7 +1,7 @@
 pub use bytemuck::Zeroable;
 pub use frenderer::{
     input::{Input, Key},
-    wgpu, BitFont, Frenderer, GPUCamera as Camera, SheetRegion, Transform,
+    wgpu, BitFont, Frenderer, GPUCamera as Camera, SheetRegion, SpriteGroupId, Transform,
 };
 pub trait Game: Sized + 'static {
     fn new(engine: &mut Engine) -> Self;
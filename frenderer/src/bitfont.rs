@@ -1,6 +1,6 @@
 use std::ops::RangeBounds;
 
-use crate::{SheetRegion, SpriteRenderer, Transform};
+use crate::{SheetRegion, SpriteGroupId, SpriteRenderer, Transform};
 
 #[derive(Clone, Copy, Debug)]
 pub struct BitFont<B: RangeBounds<char> = std::ops::RangeInclusive<char>> {
@@ -31,7 +31,7 @@ impl<B: RangeBounds<char>> BitFont<B> {
     pub fn draw_text(
         &self,
         sprites: &mut SpriteRenderer,
-        group: usize,
+        group: SpriteGroupId,
         start: usize,
         text: &str,
         mut screen_pos: [f32; 2],
@@ -62,13 +62,7 @@ impl<B: RangeBounds<char>> BitFont<B> {
             if !self.chars.contains(&chara) {
                 panic!("Drawing outside of font character range");
             }
-            *trf = Transform {
-                w: char_sz as u16,
-                h: char_sz as u16,
-                x: screen_pos[0],
-                y: screen_pos[1],
-                rot: 0.0,
-            };
+            *trf = Transform::new(screen_pos, [char_sz, char_sz], 0.0);
             let chara = u32::from(chara) - start_char;
             let which_row = chara / self.chars_per_row as u32;
             let which_col = chara % self.chars_per_row as u32;
@@ -2,16 +2,78 @@
 //! make one using [`with_default_runtime()`] or provide your own
 //! [`super::Runtime`] implementor via [`Renderer::with_runtime()`].
 
-use crate::{sprites::SpriteRenderer, WGPU};
+use crate::{
+    postprocess::{PostEffect, PostProcessor},
+    sprites::{GPUCamera, SpriteGroupId, SpriteRenderer},
+    tilemap::TilemapRenderer,
+    transition::{Transition, Transitioner},
+    WGPU,
+};
 use winit::event::{Event, WindowEvent};
 
-/// A wrapper over GPU state and (for now) a sprite renderer.
+/// A wrapper over GPU state, a sprite renderer and a tilemap renderer.
 pub struct Renderer<RT: super::Runtime> {
     pub gpu: WGPU,
     pub sprites: SpriteRenderer,
+    pub tilemaps: TilemapRenderer,
+    presentation: Presentation,
+    post: PostProcessor,
+    transition: Transitioner,
     runtime: RT,
 }
 
+/// How a [`Renderer`] fits what it draws into the window.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Presentation {
+    /// Fill the whole window, stretching the scene if the window's
+    /// aspect ratio doesn't match the cameras'.
+    #[default]
+    Stretch,
+    /// Keep the aspect ratio of a `width` by `height` virtual
+    /// resolution, scaling it as large as fits in the window and
+    /// filling the rest with black bars.
+    Letterbox { width: u32, height: u32 },
+    /// Like [`Presentation::Letterbox`], but only scale by whole
+    /// numbers (at least 1), so every virtual pixel is the same
+    /// number of window pixels across.  Use this for crisp pixel art.
+    /// A window smaller than `width` by `height` can't fit even one
+    /// step, so along whichever axis is too short the scene is
+    /// squeezed into the window instead.
+    PixelPerfect { width: u32, height: u32 },
+}
+
+impl Presentation {
+    /// The part of a window of the given size the scene is drawn
+    /// into, as `[x, y, width, height]` in window pixels from the
+    /// top left.
+    pub(crate) fn viewport(self, [win_w, win_h]: [f32; 2]) -> [f32; 4] {
+        let (width, height, scale) = match self {
+            Presentation::Stretch => return [0.0, 0.0, win_w, win_h],
+            Presentation::Letterbox { width, height } => {
+                let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+                (width, height, (win_w / width).min(win_h / height))
+            }
+            Presentation::PixelPerfect { width, height } => {
+                let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+                (
+                    width,
+                    height,
+                    (win_w / width).min(win_h / height).floor().max(1.0),
+                )
+            }
+        };
+        let (w, h) = (width * scale, height * scale);
+        // Centered on whole pixels so pixel-perfect output stays sharp,
+        // and never outside the window, which viewports can't be
+        [
+            ((win_w - w) / 2.0).floor().max(0.0),
+            ((win_h - h) / 2.0).floor().max(0.0),
+            w.min(win_w),
+            h.min(win_h),
+        ]
+    }
+}
+
 /// Initialize frenderer with default settings for the current target
 /// architecture, including logging via `env_logger` on native or `console_log` on web.
 /// On web, this also adds a canvas to the given window.  If you don't need all that behavior,
@@ -37,15 +99,54 @@ pub fn with_default_runtime(window: &winit::window::Window) -> super::Frenderer
         .expect("couldn't append canvas to document body");
     Renderer::with_runtime(window, super::WebRuntime(0))
 }
+/// Initialize a headless frenderer which draws into an offscreen
+/// texture of the given size instead of a window.  This works on any
+/// adapter, falling back to a software adapter if no GPU is present,
+/// so it's suitable for running scenes on machines without a display.
+/// Unlike [`with_default_runtime()`], this does not install a logger.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn headless(width: u32, height: u32) -> super::Frenderer {
+    Renderer::headless_with_runtime((width, height), false, super::PollsterRuntime(0))
+}
 
 impl<RT: super::Runtime> Renderer<RT> {
     /// Create a new Renderer with the given window and runtime.
     pub fn with_runtime(window: &winit::window::Window, runtime: RT) -> Self {
         let gpu = runtime.run_future(WGPU::new(window));
         let sprites = SpriteRenderer::new(&gpu);
+        let tilemaps = TilemapRenderer::new(&gpu);
+        let post = PostProcessor::new(&gpu);
+        let transition = Transitioner::new(&gpu);
         Self {
             gpu,
             sprites,
+            tilemaps,
+            presentation: Presentation::Stretch,
+            post,
+            transition,
+            runtime,
+        }
+    }
+    /// Create a new headless Renderer drawing into an offscreen
+    /// texture of the given size.  If `force_fallback_adapter` is
+    /// set, a software adapter is always used.
+    pub fn headless_with_runtime(
+        size: (u32, u32),
+        force_fallback_adapter: bool,
+        runtime: RT,
+    ) -> Self {
+        let gpu = runtime.run_future(WGPU::new_headless(size, force_fallback_adapter));
+        let sprites = SpriteRenderer::new(&gpu);
+        let tilemaps = TilemapRenderer::new(&gpu);
+        let post = PostProcessor::new(&gpu);
+        let transition = Transitioner::new(&gpu);
+        Self {
+            gpu,
+            sprites,
+            tilemaps,
+            presentation: Presentation::Stretch,
+            post,
+            transition,
             runtime,
         }
     }
@@ -64,65 +165,268 @@ impl<RT: super::Runtime> Renderer<RT> {
                 ..
             } => {
                 self.gpu.resize(size);
+                self.post.resize(&self.gpu);
+                self.transition.resize(&self.gpu);
                 true
             }
             _ => false,
         }
     }
+    /// Apply the given effects, in order, to every frame drawn by
+    /// [`Renderer::render`].  Pass an empty slice to draw straight to
+    /// the window again.
+    pub fn set_post_effects(&mut self, effects: &[PostEffect]) {
+        self.post.set_effects(&self.gpu, effects);
+    }
+    /// The effects applied to every frame; see [`Renderer::set_post_effects`].
+    pub fn post_effects(&self) -> &[PostEffect] {
+        self.post.effects()
+    }
+    /// Start transitioning from the sprite groups in `from` to those
+    /// in `to` over `duration` seconds, finishing any transition
+    /// already running.  The groups in `to` are shown right away, and
+    /// those in `from` are hidden once the transition is done; other
+    /// groups and tilemaps are drawn as usual on both sides.  Advance the
+    /// transition with [`Renderer::update_transition`].
+    pub fn start_transition(
+        &mut self,
+        transition: Transition,
+        from: &[SpriteGroupId],
+        to: &[SpriteGroupId],
+        duration: f32,
+    ) {
+        self.finish_transition();
+        for &group in to {
+            self.sprites.set_visible(group, true);
+        }
+        self.transition
+            .start(&self.gpu, transition, from, to, duration);
+    }
+    /// Advance the running transition, if any, by `dt` seconds.
+    pub fn update_transition(&mut self, dt: f32) {
+        if let Some((from, to)) = self.transition.update(&self.gpu, dt) {
+            self.hide_transitioned(&from, &to);
+        }
+    }
+    /// Skip to the end of the running transition, if any.
+    pub fn finish_transition(&mut self) {
+        if let Some((from, to)) = self.transition.finish() {
+            self.hide_transitioned(&from, &to);
+        }
+    }
+    /// Whether a transition is running.
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_active()
+    }
+    fn hide_transitioned(&mut self, from: &[SpriteGroupId], to: &[SpriteGroupId]) {
+        for &group in from {
+            if !to.contains(&group) && self.sprites.contains_sprite_group(group) {
+                self.sprites.set_visible(group, false);
+            }
+        }
+    }
+    /// Change how the scene is fit into the window.  Usually the
+    /// virtual resolution matches the size of the cameras in use.
+    pub fn set_presentation(&mut self, presentation: Presentation) {
+        self.presentation = presentation;
+    }
+    /// How the scene is fit into the window.
+    pub fn presentation(&self) -> Presentation {
+        self.presentation
+    }
+    /// The part of the window the scene is drawn into, as `[x, y,
+    /// width, height]` in window pixels from the top left.  This is
+    /// the whole window unless [`Renderer::set_presentation`] asked
+    /// for letterboxing, and always lies within the window.
+    pub fn viewport(&self) -> [f32; 4] {
+        self.presentation
+            .viewport([self.gpu.config.width as f32, self.gpu.config.height as f32])
+    }
+    /// Convert a position in window pixels (such as
+    /// [`crate::input::Input::mouse_pos`]) to world coordinates as
+    /// seen through `camera`, taking the [`Presentation`] into
+    /// account.  Positions in the black bars map to points outside
+    /// the camera's view.
+    pub fn screen_to_world(&self, camera: &GPUCamera, [x, y]: [f32; 2]) -> [f32; 2] {
+        let [vx, vy, vw, vh] = self.viewport();
+        camera.screen_to_world([x - vx, y - vy], [vw, vh])
+    }
+    /// Convert a world position seen through `camera` to window pixels.
+    pub fn world_to_screen(&self, camera: &GPUCamera, world_pos: [f32; 2]) -> [f32; 2] {
+        let [vx, vy, vw, vh] = self.viewport();
+        let [x, y] = camera.world_to_screen(world_pos, [vw, vh]);
+        [x + vx, y + vy]
+    }
     /// Acquire the next frame, create a [`wgpu::RenderPass`], draw
-    /// into it, and submit the encoder.
+    /// into it, and submit the encoder.  When headless, this draws
+    /// into [`WGPU::offscreen_texture`] instead.
     pub fn render(&self) {
         let (frame, view, mut encoder) = self.render_setup();
-        {
-            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.gpu.depth_texture_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: None,
-                }),
-            });
-            self.render_into(&mut rpass);
-        }
+        self.render_to_view(&mut encoder, &view);
         self.render_finish(frame, encoder);
     }
+    /// Clear `view` and the depth buffer and draw everything into
+    /// `view`, applying any post-processing effects.  `view` must
+    /// match the size and format of the surface (or offscreen
+    /// texture).
+    fn render_to_view(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let scene = self.post.scene_target().unwrap_or(view);
+        match self.transition.frames() {
+            Some([(from_view, without_to), (to_view, without_from)]) => {
+                self.render_sprites(encoder, from_view, without_to);
+                self.render_sprites(encoder, to_view, without_from);
+                self.transition.composite(encoder, scene);
+            }
+            None => self.render_sprites(encoder, scene, &[]),
+        }
+        if self.post.scene_target().is_some() {
+            self.post.apply(encoder, view);
+        }
+    }
+    /// Clear `view` and the depth buffer and draw every visible
+    /// tilemap, then every visible sprite group except those in
+    /// `exclude`, into `view`.
+    fn render_sprites(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        exclude: &[SpriteGroupId],
+    ) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.gpu.depth_texture_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: true,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        self.sprites.flush(&self.gpu);
+        self.tilemaps.flush(&self.gpu);
+        let [x, y, w, h] = self.viewport();
+        rpass.set_viewport(x, y, w, h, 0.0, 1.0);
+        self.tilemaps.render(&mut rpass);
+        self.sprites.render_excluding(&mut rpass, exclude);
+    }
+    /// Read back the frame produced by [`Renderer::render`] as
+    /// tightly packed 8-bit RGBA pixels, along with its width and
+    /// height.  When headless this reads the offscreen texture
+    /// directly; otherwise, since swapchain images can't be read
+    /// back, the current scene is drawn again into a temporary
+    /// texture.  Either way it blocks until the GPU is done, and it
+    /// does not work on the web.
+    pub fn capture_frame(&self) -> (Vec<u8>, (u32, u32)) {
+        let texels = match &self.gpu.offscreen_texture {
+            Some(texture) => self.gpu.read_texture(texture),
+            None => {
+                let texture = self.gpu.device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("capture"),
+                    size: wgpu::Extent3d {
+                        width: self.gpu.config.width,
+                        height: self.gpu.config.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: self.gpu.config.format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                    view_formats: &[],
+                });
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                let mut encoder = self
+                    .gpu
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+                self.render_to_view(&mut encoder, &view);
+                self.gpu.queue.submit(Some(encoder.finish()));
+                self.gpu.read_texture(&texture)
+            }
+        };
+        let rgba = match self.gpu.config.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => texels,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => {
+                let mut texels = texels;
+                for px in texels.chunks_exact_mut(4) {
+                    px.swap(0, 2);
+                }
+                texels
+            }
+            format => panic!("Can't capture frames of format {format:?}"),
+        };
+        (rgba, (self.gpu.config.width, self.gpu.config.height))
+    }
+    /// Like [`Renderer::capture_frame`], but produces an [`image::RgbaImage`].
+    pub fn capture_image(&self) -> image::RgbaImage {
+        let (rgba, (width, height)) = self.capture_frame();
+        image::RgbaImage::from_raw(width, height, rgba)
+            .expect("Captured frame has the wrong number of pixels")
+    }
+    /// Capture the current frame (see [`Renderer::capture_frame`]) and save it as a PNG file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_screenshot(&self, path: impl AsRef<std::path::Path>) -> image::ImageResult<()> {
+        self.capture_image()
+            .save_with_format(path, image::ImageFormat::Png)
+    }
     /// Renders all the frenderer stuff into a given
     /// [`wgpu::RenderPass`].  Just does rendering, no encoder
-    /// submitting or frame acquire/present.
+    /// submitting or frame acquire/present.  Sprite and tile changes
+    /// are uploaded first (see [`crate::SpriteRenderer::flush`] and
+    /// [`crate::TilemapRenderer::flush`]), and drawing is
+    /// limited to [`Renderer::viewport`].  Post-processing effects
+    /// and transitions aren't applied, since they need the whole
+    /// frame.
     pub fn render_into<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
     where
         's: 'pass,
     {
+        self.sprites.flush(&self.gpu);
+        self.tilemaps.flush(&self.gpu);
+        let [x, y, w, h] = self.viewport();
+        rpass.set_viewport(x, y, w, h, 0.0, 1.0);
+        self.tilemaps.render(rpass);
         self.sprites.render(rpass, ..);
     }
     /// Convenience method for acquiring a surface texture, view, and
-    /// command encoder
+    /// command encoder.  When headless there is no surface texture,
+    /// and the view is of the offscreen texture.
     pub fn render_setup(
         &self,
     ) -> (
-        wgpu::SurfaceTexture,
+        Option<wgpu::SurfaceTexture>,
         wgpu::TextureView,
         wgpu::CommandEncoder,
     ) {
-        let frame = self
-            .gpu
-            .surface
-            .get_current_texture()
-            .expect("Failed to acquire next swap chain texture");
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        let (frame, view) = match &self.gpu.surface {
+            Some(surface) => {
+                let frame = surface
+                    .get_current_texture()
+                    .expect("Failed to acquire next swap chain texture");
+                let view = frame
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(frame), view)
+            }
+            None => {
+                let view = self
+                    .gpu
+                    .offscreen_texture
+                    .as_ref()
+                    .expect("Headless WGPU has no offscreen texture")
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (None, view)
+            }
+        };
         let encoder = self
             .gpu
             .device
@@ -130,9 +434,143 @@ impl<RT: super::Runtime> Renderer<RT> {
         (frame, view, encoder)
     }
     /// Convenience method for submitting a command encoder and
-    /// presenting the swapchain image.
-    pub fn render_finish(&self, frame: wgpu::SurfaceTexture, encoder: wgpu::CommandEncoder) {
+    /// presenting the swapchain image (if there is one).
+    pub fn render_finish(
+        &self,
+        frame: Option<wgpu::SurfaceTexture>,
+        encoder: wgpu::CommandEncoder,
+    ) {
         self.gpu.queue.submit(Some(encoder.finish()));
-        frame.present();
+        if let Some(frame) = frame {
+            frame.present();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Presentation;
+    use crate::{BlendMode, Color, GPUCamera, SheetRegion, Transform};
+
+    #[test]
+    fn stretch_fills_the_window() {
+        assert_eq!(
+            Presentation::Stretch.viewport([1000.0, 600.0]),
+            [0.0, 0.0, 1000.0, 600.0]
+        );
+    }
+
+    #[test]
+    fn letterbox_adds_bars_on_the_long_side() {
+        let letterbox = Presentation::Letterbox {
+            width: 320,
+            height: 240,
+        };
+        // Too wide: bars on the left and right
+        assert_eq!(
+            letterbox.viewport([1000.0, 600.0]),
+            [100.0, 0.0, 800.0, 600.0]
+        );
+        // Too tall: bars on the top and bottom
+        assert_eq!(
+            letterbox.viewport([640.0, 960.0]),
+            [0.0, 240.0, 640.0, 480.0]
+        );
+        // Just right: no bars, even when scaling by a fraction
+        assert_eq!(letterbox.viewport([480.0, 360.0]), [0.0, 0.0, 480.0, 360.0]);
+    }
+
+    #[test]
+    fn pixel_perfect_scales_by_whole_numbers() {
+        let pixel_perfect = Presentation::PixelPerfect {
+            width: 320,
+            height: 240,
+        };
+        assert_eq!(
+            pixel_perfect.viewport([1000.0, 600.0]),
+            [180.0, 60.0, 640.0, 480.0]
+        );
+        // Never smaller than 1:1, but never outside the window either
+        assert_eq!(
+            pixel_perfect.viewport([200.0, 100.0]),
+            [0.0, 0.0, 200.0, 100.0]
+        );
+        // Only the axis that's too short is cut down
+        assert_eq!(
+            pixel_perfect.viewport([400.0, 200.0]),
+            [40.0, 0.0, 320.0, 200.0]
+        );
+    }
+
+    /// Draws a few sprites headlessly and compares the frame against
+    /// `testdata/sprites.png`.  It needs a GPU or software adapter, so
+    /// run it with `cargo test -- --ignored`; set `FRENDERER_BLESS=1`
+    /// to write a new golden image instead.
+    #[test]
+    #[ignore]
+    fn sprites_match_golden_image() {
+        let golden_path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/sprites.png");
+        let mut frend = crate::headless(64, 48);
+        // A 4x4 sheet of red, green, blue, and white 2x2 squares
+        let mut sheet = image::RgbaImage::new(4, 4);
+        for (x, y, px) in sheet.enumerate_pixels_mut() {
+            *px = image::Rgba(match (x / 2, y / 2) {
+                (0, 0) => [255, 0, 0, 255],
+                (1, 0) => [0, 255, 0, 255],
+                (0, 1) => [0, 0, 255, 255],
+                _ => [255, 255, 255, 255],
+            });
+        }
+        let tex = frend.gpu.create_texture(
+            sheet.as_raw(),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            sheet.dimensions(),
+            None,
+        );
+        let camera = GPUCamera::new([0.0, 0.0], [64.0, 48.0]);
+        let whole = SheetRegion::new(0, 0, 0, 8, 4, 4);
+        let opaque = frend.sprites.add_sprite_group(
+            &frend.gpu,
+            &tex,
+            vec![
+                Transform::new([16.0, 24.0], [16.0, 16.0], 0.0),
+                Transform::new([40.0, 24.0], [16.0, 16.0], 0.0),
+                Transform::new([52.0, 40.0], [8.0, 8.0], std::f32::consts::FRAC_PI_4),
+            ],
+            vec![whole, whole.flip_x(true).flip_y(true), whole],
+            camera,
+        );
+        frend.sprites.get_colors_mut(opaque)[2] = Color::rgb(255, 128, 0);
+        let translucent = frend.sprites.add_sprite_group(
+            &frend.gpu,
+            &tex,
+            vec![Transform::new([28.0, 24.0], [16.0, 32.0], 0.0)],
+            vec![SheetRegion::new(0, 2, 2, 4, 2, 2)],
+            camera,
+        );
+        frend
+            .sprites
+            .set_blend_mode(&frend.gpu, translucent, BlendMode::Alpha);
+        frend.sprites.get_colors_mut(translucent)[0] = Color::WHITE.with_alpha(128);
+        frend.render();
+        let frame = frend.capture_image();
+        if std::env::var_os("FRENDERER_BLESS").is_some() {
+            frame.save(golden_path).unwrap();
+            return;
+        }
+        let golden = image::open(golden_path).unwrap().into_rgba8();
+        assert_eq!(frame.dimensions(), golden.dimensions());
+        // Leave a little room for rounding differences between adapters
+        for (x, y, px) in frame.enumerate_pixels() {
+            let expected = golden.get_pixel(x, y);
+            assert!(
+                px.0.iter()
+                    .zip(expected.0.iter())
+                    .all(|(a, b)| a.abs_diff(*b) <= 2),
+                "pixel ({x}, {y}) is {:?} but should be {:?}",
+                px.0,
+                expected.0
+            );
+        }
     }
 }
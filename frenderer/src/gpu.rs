@@ -4,22 +4,195 @@
 //! so that it can be provided by client code rather than initialized
 //! solely within frenderer.
 
-use crate::USE_STORAGE;
+use crate::{mipmap, USE_STORAGE};
+use std::collections::HashSet;
+use std::sync::Mutex;
 
 /// A wrapper for a WGPU instance, surface, adapter, device, queue, and surface configuration.
+///
+/// A headless `WGPU` (see [`WGPU::new_headless`]) has no surface;
+/// instead it renders into [`WGPU::offscreen_texture`], and
+/// [`WGPU::config`] only records that texture's format and size.
 #[allow(dead_code)]
 pub struct WGPU {
     instance: wgpu::Instance,
-    pub surface: wgpu::Surface,
+    pub surface: Option<wgpu::Surface>,
     adapter: wgpu::Adapter,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
     pub depth_texture: wgpu::Texture,
     pub depth_texture_view: wgpu::TextureView,
+    /// The color target used in place of a swapchain when running headless
+    pub offscreen_texture: Option<wgpu::Texture>,
+    mipmaps: mipmap::MipmapGenerator,
+    /// Single-layer textures allocated with a spare layer on GL; see
+    /// [`WGPU::texture_layers`].
+    padded_textures: Mutex<HashSet<wgpu::Id<wgpu::Texture>>>,
 }
 
+/// An error describing why texture data couldn't be uploaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextureError {
+    /// No layers were provided.
+    NoLayers,
+    /// A layer has a width or height of zero.
+    EmptyLayer { layer: usize },
+    /// A layer's data isn't the right length for its size and format.
+    WrongDataLength {
+        layer: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// A layer is larger than the GPU's maximum texture size.
+    TooLarge {
+        layer: usize,
+        size: (u32, u32),
+        max_size: u32,
+    },
+    /// There are more layers than the GPU supports in one array texture.
+    TooManyLayers { layers: u32, max_layers: u32 },
+    /// The format has no single block size, e.g. a combined depth/stencil format.
+    UnsupportedFormat(wgpu::TextureFormat),
+    /// A layer's size isn't a multiple of its compressed format's block size.
+    NotBlockAligned {
+        layer: usize,
+        size: (u32, u32),
+        block_size: (u32, u32),
+    },
+    /// Mipmaps were requested for a format that can't be rendered to.
+    CantMipmap(wgpu::TextureFormat),
+    /// The texture being written to doesn't have the given layer.
+    LayerOutOfRange { layer: u32, layers: u32 },
+    /// The rectangle being written doesn't fit inside the texture.
+    OutOfBounds {
+        origin: (u32, u32),
+        size: (u32, u32),
+        texture_size: (u32, u32),
+    },
+}
+
+/// Checks that `data` holds a `width` by `height` image in `format`,
+/// returning the number of bytes in each of its rows.
+fn check_layer_data(
+    format: wgpu::TextureFormat,
+    layer: usize,
+    data: &[u8],
+    (width, height): (u32, u32),
+) -> Result<u32, TextureError> {
+    let block_size = format.block_dimensions();
+    if width % block_size.0 != 0 || height % block_size.1 != 0 {
+        return Err(TextureError::NotBlockAligned {
+            layer,
+            size: (width, height),
+            block_size,
+        });
+    }
+    let (bytes_per_row, rows) = texture_data_layout(format, (width, height))
+        .ok_or(TextureError::UnsupportedFormat(format))?;
+    let expected = bytes_per_row as usize * rows as usize;
+    // Data read back from the GPU has rows padded for copies; take that too.
+    let padded = padded_bytes_per_row(bytes_per_row);
+    if data.len() == expected {
+        Ok(bytes_per_row)
+    } else if data.len() == padded as usize * rows as usize {
+        Ok(padded)
+    } else {
+        Err(TextureError::WrongDataLength {
+            layer,
+            expected,
+            actual: data.len(),
+        })
+    }
+}
+
+/// How the bytes of a `width` by `height` image in `format` are laid
+/// out: the number of bytes in each row of blocks (one row of pixels
+/// for uncompressed formats), and the number of such rows.  Returns
+/// `None` for formats without a single block size.
+pub fn texture_data_layout(
+    format: wgpu::TextureFormat,
+    (width, height): (u32, u32),
+) -> Option<(u32, u32)> {
+    let block_size = format.block_size(None)?;
+    let (block_w, block_h) = format.block_dimensions();
+    Some((
+        width.div_ceil(block_w) * block_size,
+        height.div_ceil(block_h),
+    ))
+}
+
+/// Rounds `bytes_per_row` up to wgpu's required alignment for copies
+/// between buffers and textures.
+pub fn padded_bytes_per_row(bytes_per_row: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    bytes_per_row.div_ceil(align) * align
+}
+
+impl std::fmt::Display for TextureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextureError::NoLayers => write!(f, "no texture layers were given"),
+            TextureError::EmptyLayer { layer } => write!(f, "layer {layer} has no pixels"),
+            TextureError::WrongDataLength {
+                layer,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "layer {layer} should have {expected} bytes of data but has {actual}"
+            ),
+            TextureError::TooLarge {
+                layer,
+                size: (w, h),
+                max_size,
+            } => write!(
+                f,
+                "layer {layer} is {w}x{h}, but textures can be at most {max_size}x{max_size}"
+            ),
+            TextureError::TooManyLayers { layers, max_layers } => write!(
+                f,
+                "{layers} layers were given, but array textures can have at most {max_layers}"
+            ),
+            TextureError::UnsupportedFormat(format) => {
+                write!(f, "can't upload texture data in {format:?}")
+            }
+            TextureError::NotBlockAligned {
+                layer,
+                size: (w, h),
+                block_size: (bw, bh),
+            } => write!(
+                f,
+                "layer {layer} is {w}x{h}, which isn't a multiple of its format's {bw}x{bh} blocks"
+            ),
+            TextureError::CantMipmap(format) => {
+                write!(f, "can't generate mipmaps for {format:?} textures")
+            }
+            TextureError::LayerOutOfRange { layer, layers } => {
+                write!(
+                    f,
+                    "can't write to layer {layer} of a texture with {layers} layers"
+                )
+            }
+            TextureError::OutOfBounds {
+                origin: (x, y),
+                size: (w, h),
+                texture_size: (tw, th),
+            } => write!(
+                f,
+                "a {w}x{h} rectangle at ({x}, {y}) doesn't fit in a {tw}x{th} texture"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
 impl WGPU {
+    /// Create an array texture from `images`, each of which must be
+    /// `width` by `height` pixels.  Panics if the images don't match
+    /// that size; see [`WGPU::create_layered_texture`] for a version
+    /// which accepts differently sized images and reports errors.
     pub fn create_array_texture(
         &self,
         images: &[&[u8]],
@@ -27,51 +200,259 @@ impl WGPU {
         (width, height): (u32, u32),
         label: Option<&str>,
     ) -> wgpu::Texture {
+        let layers: Vec<_> = images.iter().map(|img| (*img, (width, height))).collect();
+        self.create_array_texture_inner(&layers, format, label, false)
+            .unwrap_or_else(|e| panic!("Couldn't create array texture: {e}"))
+    }
+    /// Like [`WGPU::create_array_texture`], but also generates a full
+    /// chain of mipmaps for every layer on the GPU.  Pair this with a
+    /// sampler whose `mipmap_filter` is [`wgpu::FilterMode::Linear`]
+    /// (e.g. [`crate::sampler_descriptor`] with linear filtering) for
+    /// trilinear filtering, so that textures drawn much smaller than
+    /// their actual size don't shimmer.  The format must be renderable.
+    pub fn create_array_texture_with_mipmaps(
+        &self,
+        images: &[&[u8]],
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        label: Option<&str>,
+    ) -> wgpu::Texture {
+        let layers: Vec<_> = images.iter().map(|img| (*img, (width, height))).collect();
+        self.create_array_texture_inner(&layers, format, label, true)
+            .unwrap_or_else(|e| panic!("Couldn't create array texture: {e}"))
+    }
+    /// Create an array texture from images of different sizes, given
+    /// as `(data, (width, height))` pairs.  Every layer is as large as
+    /// the largest image, and each image sits in the top left corner
+    /// of its layer with the rest left transparent, so
+    /// [`crate::SheetRegion`] pixel coordinates work the same way on
+    /// every layer.
+    ///
+    /// Each layer's data is rows of texels (or blocks, for compressed
+    /// formats) in `format`, either tightly packed or with each row
+    /// padded to [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`] bytes as in
+    /// data read back from the GPU.
+    pub fn create_layered_texture(
+        &self,
+        layers: &[(&[u8], (u32, u32))],
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, TextureError> {
+        self.create_array_texture_inner(layers, format, label, false)
+    }
+    /// Like [`WGPU::create_layered_texture`], but with a full chain of
+    /// mipmaps; see [`WGPU::create_array_texture_with_mipmaps`].
+    pub fn create_layered_texture_with_mipmaps(
+        &self,
+        layers: &[(&[u8], (u32, u32))],
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+    ) -> Result<wgpu::Texture, TextureError> {
+        self.create_array_texture_inner(layers, format, label, true)
+    }
+    /// Regenerate all the mip levels of `texture` from its first
+    /// level.  `texture` must have been created by
+    /// [`WGPU::create_array_texture_with_mipmaps`] or
+    /// [`WGPU::create_texture_with_mipmaps`].
+    pub fn generate_mipmaps(&self, texture: &wgpu::Texture) {
+        self.mipmaps.generate(&self.device, &self.queue, texture);
+    }
+    fn create_array_texture_inner(
+        &self,
+        layers: &[(&[u8], (u32, u32))],
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+        mipmaps: bool,
+    ) -> Result<wgpu::Texture, TextureError> {
+        if layers.is_empty() {
+            return Err(TextureError::NoLayers);
+        }
+        if texture_data_layout(format, (1, 1)).is_none() {
+            return Err(TextureError::UnsupportedFormat(format));
+        }
+        if mipmaps
+            && !self
+                .adapter
+                .get_texture_format_features(format)
+                .allowed_usages
+                .contains(wgpu::TextureUsages::RENDER_ATTACHMENT)
+        {
+            return Err(TextureError::CantMipmap(format));
+        }
+        let limits = self.device.limits();
+        if layers.len() as u32 > limits.max_texture_array_layers {
+            return Err(TextureError::TooManyLayers {
+                layers: layers.len() as u32,
+                max_layers: limits.max_texture_array_layers,
+            });
+        }
+        for (layer, &(data, (w, h))) in layers.iter().enumerate() {
+            if w == 0 || h == 0 {
+                return Err(TextureError::EmptyLayer { layer });
+            }
+            if w > limits.max_texture_dimension_2d || h > limits.max_texture_dimension_2d {
+                return Err(TextureError::TooLarge {
+                    layer,
+                    size: (w, h),
+                    max_size: limits.max_texture_dimension_2d,
+                });
+            }
+            check_layer_data(format, layer, data, (w, h))?;
+        }
+        let width = layers.iter().map(|(_, (w, _))| *w).max().unwrap();
+        let height = layers.iter().map(|(_, (_, h))| *h).max().unwrap();
         let size = wgpu::Extent3d {
             width,
             height,
-            depth_or_array_layers: images.len() as u32,
+            depth_or_array_layers: layers.len() as u32,
         };
+        // The GL backend makes single-layer textures plain 2D
+        // textures, which can't be viewed as the 2D arrays our shaders
+        // sample from; give them a second, unused layer.
+        let alloc_size =
+            if layers.len() == 1 && self.adapter.get_info().backend == wgpu::Backend::Gl {
+                wgpu::Extent3d {
+                    depth_or_array_layers: 2,
+                    ..size
+                }
+            } else {
+                size
+            };
         let texture = self.device.create_texture(&wgpu::TextureDescriptor {
             label,
-            size,
-            mip_level_count: 1,
+            size: alloc_size,
+            mip_level_count: if mipmaps {
+                mipmap::mip_level_count(width, height)
+            } else {
+                1
+            },
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            usage: if mipmaps {
+                wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
+            } else {
+                wgpu::TextureUsages::empty()
+            } | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
             view_formats: &[],
         });
-        if images.len() == 1 {
-            self.queue.write_texture(
-                texture.as_image_copy(),
-                images[0],
-                wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * width),
-                    rows_per_image: Some(height),
-                },
-                size,
-            );
+        if alloc_size != size {
+            self.padded_textures
+                .lock()
+                .unwrap()
+                .insert(texture.global_id());
+        }
+        // New textures start out zeroed (i.e. transparent), so smaller
+        // layers only need their own pixels written.
+        for (layer, &(data, (w, h))) in layers.iter().enumerate() {
+            let bytes_per_row = check_layer_data(format, layer, data, (w, h))?;
+            self.write_layer(&texture, layer as u32, (0, 0), data, (w, h), bytes_per_row);
+        }
+        if mipmaps {
+            self.generate_mipmaps(&texture);
+        }
+        Ok(texture)
+    }
+    /// Replace a `width` by `height` rectangle of pixels in one layer
+    /// of `texture`, with its top left corner at `(x, y)`.  `data` is
+    /// laid out as in [`WGPU::create_layered_texture`] and must be in
+    /// the texture's format.  Sprite groups drawing from `texture`
+    /// see the new pixels from the next frame on, and if the texture
+    /// has mipmaps they're regenerated to match.
+    ///
+    /// Regenerating mipmaps redraws every layer, so prefer textures
+    /// without mipmaps for things that change every frame.
+    pub fn write_texture_region(
+        &self,
+        texture: &wgpu::Texture,
+        layer: u32,
+        (x, y): (u32, u32),
+        data: &[u8],
+        (width, height): (u32, u32),
+    ) -> Result<(), TextureError> {
+        let layers = self.texture_layers(texture);
+        if layer >= layers {
+            return Err(TextureError::LayerOutOfRange { layer, layers });
+        }
+        if width == 0 || height == 0 {
+            return Err(TextureError::EmptyLayer {
+                layer: layer as usize,
+            });
+        }
+        if !matches!(x.checked_add(width), Some(right) if right <= texture.width())
+            || !matches!(y.checked_add(height), Some(bottom) if bottom <= texture.height())
+        {
+            return Err(TextureError::OutOfBounds {
+                origin: (x, y),
+                size: (width, height),
+                texture_size: (texture.width(), texture.height()),
+            });
+        }
+        let format = texture.format();
+        if texture_data_layout(format, (1, 1)).is_none() {
+            return Err(TextureError::UnsupportedFormat(format));
+        }
+        let (block_w, block_h) = format.block_dimensions();
+        if x % block_w != 0 || y % block_h != 0 {
+            return Err(TextureError::NotBlockAligned {
+                layer: layer as usize,
+                size: (x, y),
+                block_size: (block_w, block_h),
+            });
+        }
+        let bytes_per_row = check_layer_data(format, layer as usize, data, (width, height))?;
+        self.write_layer(texture, layer, (x, y), data, (width, height), bytes_per_row);
+        if texture.mip_level_count() > 1 {
+            self.generate_mipmaps(texture);
+        }
+        Ok(())
+    }
+    /// The number of layers `texture` was created with.  This is its
+    /// `depth_or_array_layers`, except for single-layer textures made
+    /// on GL, which are allocated with a second layer that shouldn't
+    /// be written to.
+    pub fn texture_layers(&self, texture: &wgpu::Texture) -> u32 {
+        if self
+            .padded_textures
+            .lock()
+            .unwrap()
+            .contains(&texture.global_id())
+        {
+            1
         } else {
-            let image_combined_len: usize = images.iter().map(|img| img.len()).sum();
-            let mut staging = Vec::with_capacity(image_combined_len);
-            for img in images {
-                staging.extend_from_slice(img);
-            }
-            // TODO Fixme this will also make a copy, it might be better to do multiple write_texture calls or else take an images[] slice which is already dense in memory
-            self.queue.write_texture(
-                texture.as_image_copy(),
-                &staging,
-                wgpu::ImageDataLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * width),
-                    rows_per_image: Some(height),
-                },
-                size,
-            );
+            texture.depth_or_array_layers()
         }
-        texture
+    }
+    fn write_layer(
+        &self,
+        texture: &wgpu::Texture,
+        layer: u32,
+        (x, y): (u32, u32),
+        data: &[u8],
+        (w, h): (u32, u32),
+        bytes_per_row: u32,
+    ) {
+        let (_, rows) = texture_data_layout(texture.format(), (w, h)).unwrap();
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: layer },
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(bytes_per_row),
+                rows_per_image: Some(rows),
+            },
+            wgpu::Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: 1,
+            },
+        );
     }
     pub fn create_texture(
         &self,
@@ -82,6 +463,17 @@ impl WGPU {
     ) -> wgpu::Texture {
         self.create_array_texture(&[image], format, (width, height), label)
     }
+    /// Like [`WGPU::create_texture`], but with a full chain of mipmaps;
+    /// see [`WGPU::create_array_texture_with_mipmaps`].
+    pub fn create_texture_with_mipmaps(
+        &self,
+        image: &[u8],
+        format: wgpu::TextureFormat,
+        (width, height): (u32, u32),
+        label: Option<&str>,
+    ) -> wgpu::Texture {
+        self.create_array_texture_with_mipmaps(&[image], format, (width, height), label)
+    }
     /// Initialize [`wgpu`] with the given [`winit::window::Window`].
     pub(crate) async fn new(window: &winit::window::Window) -> Self {
         let size = window.inner_size();
@@ -101,7 +493,99 @@ impl WGPU {
             .await
             .expect("Failed to find an appropriate adapter");
 
-        // Create the logical device and command queue
+        let (device, queue) = Self::request_device(&adapter).await;
+
+        let swapchain_capabilities = surface.get_capabilities(&adapter);
+        let swapchain_format = swapchain_capabilities.formats[0];
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: swapchain_format,
+            width: size.width,
+            height: size.height,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: swapchain_capabilities.alpha_modes[0],
+            view_formats: vec![],
+        };
+
+        surface.configure(&device, &config);
+        let (depth_texture, depth_texture_view) = Self::create_depth_texture(&device, &config);
+        let mipmaps = mipmap::MipmapGenerator::new(&device);
+        Self {
+            instance,
+            surface: Some(surface),
+            adapter,
+            device,
+            queue,
+            config,
+            depth_texture,
+            depth_texture_view,
+            offscreen_texture: None,
+            mipmaps,
+            padded_textures: Mutex::new(HashSet::new()),
+        }
+    }
+    /// Initialize [`wgpu`] without a window, rendering into an
+    /// offscreen texture of the given size.  If no hardware adapter is
+    /// available (or `force_fallback_adapter` is set), a software
+    /// fallback adapter is used instead.
+    pub(crate) async fn new_headless(
+        (width, height): (u32, u32),
+        force_fallback_adapter: bool,
+    ) -> Self {
+        log::info!("Use storage? {:?}", USE_STORAGE);
+
+        let instance = wgpu::Instance::default();
+        let mut adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                force_fallback_adapter,
+                compatible_surface: None,
+            })
+            .await;
+        if adapter.is_none() && !force_fallback_adapter {
+            log::info!("No hardware adapter found, trying a fallback adapter");
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    force_fallback_adapter: true,
+                    compatible_surface: None,
+                })
+                .await;
+        }
+        let adapter = adapter.expect("Failed to find an appropriate adapter");
+        log::info!("Headless adapter: {:?}", adapter.get_info());
+
+        let (device, queue) = Self::request_device(&adapter).await;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: Self::HEADLESS_FORMAT,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::AutoVsync,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            view_formats: vec![],
+        };
+        let offscreen_texture = Self::create_offscreen_texture(&device, &config);
+        let (depth_texture, depth_texture_view) = Self::create_depth_texture(&device, &config);
+        let mipmaps = mipmap::MipmapGenerator::new(&device);
+        Self {
+            instance,
+            surface: None,
+            adapter,
+            device,
+            queue,
+            config,
+            depth_texture,
+            depth_texture_view,
+            offscreen_texture: Some(offscreen_texture),
+            mipmaps,
+            padded_textures: Mutex::new(HashSet::new()),
+        }
+    }
+    /// Create the logical device and command queue
+    async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
@@ -127,31 +611,110 @@ impl WGPU {
                 && device.limits().max_storage_buffers_per_shader_stage > 0;
             assert!(supports_storage_resources, "Storage buffers not supported");
         }
-        let swapchain_capabilities = surface.get_capabilities(&adapter);
-        let swapchain_format = swapchain_capabilities.formats[0];
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: swapchain_format,
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::AutoVsync,
-            alpha_mode: swapchain_capabilities.alpha_modes[0],
-            view_formats: vec![],
-        };
+        (device, queue)
+    }
+    /// The color format used for the offscreen texture of a headless [`WGPU`].
+    pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
 
-        surface.configure(&device, &config);
-        let (depth_texture, depth_texture_view) = Self::create_depth_texture(&device, &config);
-        Self {
-            instance,
-            surface,
-            adapter,
-            device,
-            queue,
-            config,
-            depth_texture,
-            depth_texture_view,
+    fn create_offscreen_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+    /// A texture the size and format of the surface (or offscreen
+    /// texture), for drawing a frame into and then sampling it.
+    pub(crate) fn create_frame_target(&self, label: &str) -> wgpu::TextureView {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+    /// Copy the first layer of `texture` back to the CPU, returning its
+    /// texels as tightly packed rows (i.e., without wgpu's 256-byte
+    /// row padding).  The texture must have been created with
+    /// [`wgpu::TextureUsages::COPY_SRC`].  This blocks until the GPU
+    /// has finished all submitted work, so it's only suitable for
+    /// screenshots and tests, and it does not work on the web.
+    pub fn read_texture(&self, texture: &wgpu::Texture) -> Vec<u8> {
+        let (width, height) = (texture.width(), texture.height());
+        let (unpadded_bytes_per_row, rows) = texture_data_layout(texture.format(), (width, height))
+            .expect("Can't read back a depth/stencil texture");
+        let padded_bytes_per_row = padded_bytes_per_row(unpadded_bytes_per_row);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: (padded_bytes_per_row * rows) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(rows),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(Some(encoder.finish()));
+        let slice = buffer.slice(..);
+        let (tx, rx) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            tx.send(result).unwrap();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        rx.recv()
+            .expect("Readback buffer was never mapped")
+            .expect("Failed to map readback buffer");
+        let mut texels = Vec::with_capacity((unpadded_bytes_per_row * rows) as usize);
+        {
+            let padded = slice.get_mapped_range();
+            for row in padded.chunks_exact(padded_bytes_per_row as usize) {
+                texels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
         }
+        buffer.unmap();
+        texels
+    }
+    /// Whether this [`WGPU`] renders offscreen rather than to a window surface.
+    pub fn is_headless(&self) -> bool {
+        self.surface.is_none()
     }
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
@@ -178,11 +741,17 @@ impl WGPU {
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         (texture, view)
     }
-    /// Resize the WGPU surface
+    /// Resize the WGPU surface (or offscreen texture, if headless)
     pub(crate) fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
         self.config.width = size.width;
         self.config.height = size.height;
-        self.surface.configure(&self.device, &self.config);
+        match &self.surface {
+            Some(surface) => surface.configure(&self.device, &self.config),
+            None => {
+                self.offscreen_texture =
+                    Some(Self::create_offscreen_texture(&self.device, &self.config))
+            }
+        }
         let (depth_tex, depth_view) = Self::create_depth_texture(&self.device, &self.config);
         self.depth_texture = depth_tex;
         self.depth_texture_view = depth_view;
@@ -15,9 +15,14 @@
 //! [`sprites::SpriteRenderer::add_sprite_group()`] on the resulting
 //! [`frenderer::Renderer`] value, and eventually call
 //! [`frenderer::Renderer::process_window_event()`],
-//! [`sprites::SpriteRenderer::upload_sprites()`], and
+//! [`sprites::SpriteRenderer::get_sprites_mut()`], and
 //! [`frenderer::Renderer::render`] or
-//! [`frenderer::Renderer::render_into`] to draw.
+//! [`frenderer::Renderer::render_into`] to draw.  Sprite changes are
+//! uploaded to the GPU automatically just before drawing.
+//!
+//! Frenderer can also run without a window: [`frenderer::headless()`]
+//! creates a renderer that draws into an offscreen texture on any
+//! available adapter, including a software fallback.
 //!
 //! In the future, more types of renderers including 3D renderers will
 //! also be provided.
@@ -31,11 +36,22 @@ pub(crate) const USE_STORAGE: bool = true;
 pub(crate) const USE_STORAGE: bool = false;
 
 mod gpu;
-pub use gpu::WGPU;
+mod mipmap;
+pub use gpu::{padded_bytes_per_row, texture_data_layout, TextureError, WGPU};
+pub use half;
+pub use image;
 pub use wgpu;
 
+pub mod postprocess;
+pub use postprocess::PostEffect;
 mod sprites;
-pub use sprites::{GPUCamera, SheetRegion, SpriteRenderer, Transform};
+pub use sprites::{
+    sampler_descriptor, BlendMode, Color, GPUCamera, MaterialId, SheetRegion, SpriteGroupId,
+    SpriteRenderer, Transform,
+};
+pub mod materials;
+pub mod transition;
+pub use transition::{Transition, WipeDirection};
 
 /// A runtime for frenderer; mainly wraps an async runtime, but also sets up logging, etc.
 /// In the future it might be responsible for setting up WGPU/providing a rendering context as well.
@@ -69,3 +85,9 @@ pub type Frenderer = Renderer<PollsterRuntime>;
 pub type Frenderer = Renderer<WebRuntime>;
 pub mod bitfont;
 pub use bitfont::BitFont;
+pub mod nineslice;
+pub use nineslice::NineSlice;
+pub mod tilemap;
+pub use tilemap::{TilemapId, TilemapRenderer, Tileset, EMPTY_TILE};
+pub mod packer;
+pub use packer::AtlasBuilder;
@@ -0,0 +1,97 @@
+//! Ready-made materials for [`crate::SpriteRenderer::add_material`],
+//! along with the parameters each one expects in
+//! [`crate::SpriteRenderer::set_material_params`].  Their sources
+//! are also a starting point for writing your own.
+//!
+//! Register each one with the size of its parameters:
+//!
+//! ```ignore
+//! let dissolve = sprites.add_material(&gpu, DISSOLVE, std::mem::size_of::<DissolveParams>());
+//! ```
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::Color;
+
+/// Eats away at sprites in blotches of noise as
+/// [`DissolveParams::threshold`] rises, with a solid-colored edge.
+pub const DISSOLVE: &str = include_str!("materials/dissolve.wgsl");
+/// Replaces up to four colors with others; see [`PaletteSwapParams`].
+pub const PALETTE_SWAP: &str = include_str!("materials/palette_swap.wgsl");
+/// Sways rows of texels from side to side; see [`RippleParams`].
+/// Sprites can pick up texels from next to their sheet regions, so
+/// leave some empty space around them.
+pub const RIPPLE: &str = include_str!("materials/ripple.wgsl");
+
+/// Parameters for [`DISSOLVE`].
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, PartialEq)]
+pub struct DissolveParams {
+    /// The color of the edge, in linear RGB (alpha is ignored).
+    pub edge_color: [f32; 4],
+    /// How much of the sprite is gone, from `0.0` to `1.0`.
+    pub threshold: f32,
+    /// How much of that range the edge covers, e.g. `0.05`.
+    pub edge_width: f32,
+}
+
+/// Parameters for [`PALETTE_SWAP`].  Colors are compared after
+/// sampling, which for sRGB textures means in linear RGB; build these
+/// with [`PaletteSwapParams::new`] to swap colors as they appear in
+/// an sRGB image.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, PartialEq)]
+pub struct PaletteSwapParams {
+    /// Colors to replace; slots with zero alpha are unused.
+    pub find: [[f32; 4]; 4],
+    /// What to replace each color in `find` with.
+    pub replace: [[f32; 4]; 4],
+}
+
+impl PaletteSwapParams {
+    /// Swap each color in the first half of a pair for the second,
+    /// reading them as sRGB (as in an image editor).  The alpha of
+    /// the pairs is ignored.  Panics if there are more than four
+    /// pairs.
+    pub fn new(swaps: &[(Color, Color)]) -> Self {
+        assert!(
+            swaps.len() <= 4,
+            "a palette swap can only replace 4 colors, not {}",
+            swaps.len()
+        );
+        let mut params = Self::zeroed();
+        for (i, (find, replace)) in swaps.iter().enumerate() {
+            params.find[i] = srgb_to_linear(*find);
+            params.replace[i] = srgb_to_linear(*replace);
+        }
+        params
+    }
+}
+
+/// Parameters for [`RIPPLE`].
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, PartialEq)]
+pub struct RippleParams {
+    /// How far rows are pushed sideways, in texels.
+    pub amplitude: f32,
+    /// The distance between wave crests, in texels.
+    pub wavelength: f32,
+    /// Wave crests passing by per second.
+    pub speed: f32,
+    /// The current time in seconds; advance this every frame to
+    /// animate the ripple.
+    pub time: f32,
+}
+
+/// An opaque linear RGB color matching an sRGB [`Color`].
+fn srgb_to_linear(color: Color) -> [f32; 4] {
+    let channel = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    [channel(color.r), channel(color.g), channel(color.b), 1.0]
+}
@@ -0,0 +1,44 @@
+// Eats away at sprites in blotches, with a glowing edge.
+
+struct Dissolve {
+    edge_color: vec4<f32>,
+    // 0.0 is untouched and 1.0 is fully dissolved
+    threshold: f32,
+    // how much of the noise range the edge covers
+    edge_width: f32,
+}
+
+@group(2) @binding(0)
+var<uniform> dissolve: Dissolve;
+
+// How many texels across each blotch of noise is
+const DISSOLVE_SCALE: f32 = 4.0;
+
+fn dissolve_hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+// Value noise in 0..1, fixed to the spritesheet so it doesn't crawl
+// as sprites move
+fn dissolve_noise(p: vec2<f32>) -> f32 {
+    let cell = floor(p);
+    let f = fract(p);
+    let t = f * f * (3.0 - 2.0 * f);
+    let a = dissolve_hash(cell);
+    let b = dissolve_hash(cell + vec2(1.0, 0.0));
+    let c = dissolve_hash(cell + vec2(0.0, 1.0));
+    let d = dissolve_hash(cell + vec2(1.0, 1.0));
+    return mix(mix(a, b, t.x), mix(c, d, t.x), t.y);
+}
+
+@fragment
+fn fs_material(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index) * in.tint;
+    let texel = in.tex_coords * vec2<f32>(textureDimensions(t_diffuse));
+    let n = dissolve_noise(texel / DISSOLVE_SCALE);
+    if color.w < 0.2 || n < dissolve.threshold { discard; }
+    if dissolve.threshold > 0.0 && n < dissolve.threshold + dissolve.edge_width {
+        return vec4(dissolve.edge_color.rgb, color.w);
+    }
+    return color;
+}
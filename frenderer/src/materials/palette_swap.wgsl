@@ -0,0 +1,28 @@
+// Replaces up to four colors of a sprite with others, e.g. to recolor
+// a character's clothes.
+
+struct PaletteSwap {
+    // slots whose alpha is 0 are ignored
+    find: array<vec4<f32>, 4>,
+    replace: array<vec4<f32>, 4>,
+}
+
+@group(2) @binding(0)
+var<uniform> palette: PaletteSwap;
+
+// How close (in linear RGB) a texel must be to a color to be swapped
+const PALETTE_TOLERANCE: f32 = 0.01;
+
+@fragment
+fn fs_material(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    for (var i = 0; i < 4; i++) {
+        if palette.find[i].a > 0.0 && distance(color.rgb, palette.find[i].rgb) < PALETTE_TOLERANCE {
+            color = vec4(palette.replace[i].rgb, color.a);
+            break;
+        }
+    }
+    color *= in.tint;
+    if color.w < 0.2 { discard; }
+    return color;
+}
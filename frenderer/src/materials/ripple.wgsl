@@ -0,0 +1,26 @@
+// Sways rows of texels back and forth, like a reflection in water.
+
+struct Ripple {
+    // how far rows are pushed sideways, in texels
+    amplitude: f32,
+    // the distance between wave crests, in texels
+    wavelength: f32,
+    // wave crests passing by per second
+    speed: f32,
+    // seconds; advance this every frame to animate the ripple
+    time: f32,
+}
+
+@group(2) @binding(0)
+var<uniform> ripple: Ripple;
+
+@fragment
+fn fs_material(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+    let row = in.tex_coords.y * size.y;
+    let phase = row / max(ripple.wavelength, 1.0) + ripple.time * ripple.speed;
+    let shift = sin(phase * 6.2831853) * ripple.amplitude / size.x;
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords + vec2(shift, 0.0), in.tex_index) * in.tint;
+    if color.w < 0.2 { discard; }
+    return color;
+}
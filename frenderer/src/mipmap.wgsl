@@ -0,0 +1,28 @@
+// Draws one triangle covering the whole target, sampling the
+// previous (larger) mip level of the same array layer.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var t_src: texture_2d_array<f32>;
+@group(0) @binding(1)
+var s_src: sampler;
+@group(0) @binding(2)
+var<uniform> layer: vec4<u32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // (0,0), (2,0), (0,2) in texture space
+    let uv = vec2(f32((in_vertex_index << 1u) & 2u), f32(in_vertex_index & 2u));
+    let pos = vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return VertexOutput(pos, uv);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The source texture is a copy of just the previous mip level.
+    return textureSampleLevel(t_src, s_src, in.tex_coords, layer.x, 0.0);
+}
@@ -0,0 +1,161 @@
+//! Resizable bordered panels, such as dialog boxes and buttons, drawn
+//! from a single sheet region.  A [`NineSlice`] fills nine sprites in
+//! an existing sprite group, so panels can share a group (and a
+//! texture) with the rest of a UI.
+
+use crate::{SheetRegion, SpriteGroupId, SpriteRenderer, Transform};
+
+/// A bordered panel drawn from a single [`SheetRegion`] at any size.
+/// The region is cut into a 3x3 grid by its border insets: the
+/// corners are drawn unstretched, the edges stretch along their
+/// length, and the center stretches both ways.
+#[derive(Clone, Copy, Debug)]
+pub struct NineSlice {
+    region: SheetRegion,
+    // left, right, top, and bottom, in sheet pixels
+    insets: [u16; 4],
+    border_scale: f32,
+}
+
+impl NineSlice {
+    /// How many sprites [`NineSlice::draw`] uses.
+    pub const SPRITE_COUNT: usize = 9;
+    /// Creates a nine-slice from `region` and the widths of its left,
+    /// right, top, and bottom borders in sheet pixels.  Panics if the
+    /// borders overlap or the region is rotated.
+    pub fn with_sheet_region(region: SheetRegion, [left, right, top, bottom]: [u16; 4]) -> Self {
+        assert!(
+            !region.is_rotated(),
+            "Can't nine-slice a rotated sheet region"
+        );
+        assert!(
+            u32::from(left) + u32::from(right) <= u32::from(region.w)
+                && u32::from(top) + u32::from(bottom) <= u32::from(region.h),
+            "Nine-slice borders are larger than the sheet region"
+        );
+        Self {
+            region,
+            insets: [left, right, top, bottom],
+            border_scale: 1.0,
+        }
+    }
+    /// Produce a new [`NineSlice`] whose borders are drawn `scale`
+    /// world units wide per sheet pixel (1.0 by default).
+    pub fn with_border_scale(self, scale: f32) -> Self {
+        Self {
+            border_scale: scale,
+            ..self
+        }
+    }
+    /// Draws a panel of the given `size` into the sprites of `group`
+    /// starting at `start`.  The given position is the top-left
+    /// corner of the panel.  If the panel is smaller than its
+    /// borders, they shrink to fit.  Panics if the group has fewer
+    /// than `start + NineSlice::SPRITE_COUNT` sprites.  Returns the
+    /// number of sprites used.
+    pub fn draw(
+        &self,
+        sprites: &mut SpriteRenderer,
+        group: SpriteGroupId,
+        start: usize,
+        [x, y]: [f32; 2],
+        [w, h]: [f32; 2],
+    ) -> usize {
+        let region = self.region;
+        let [left, right, top, bottom] = self.insets;
+        // Columns left to right and rows top to bottom, in the sheet
+        let mut sheet_cols = [
+            (region.x, left),
+            (region.x + left, region.w - left - right),
+            (region.x + region.w - right, right),
+        ];
+        let mut sheet_rows = [
+            (region.y, top),
+            (region.y + top, region.h - top - bottom),
+            (region.y + region.h - bottom, bottom),
+        ];
+        // A flipped region shows its borders on the opposite sides
+        if region.is_flipped_x() {
+            sheet_cols.reverse();
+        }
+        if region.is_flipped_y() {
+            sheet_rows.reverse();
+        }
+        let cols = self.spans(x, w, sheet_cols[0].1, sheet_cols[2].1);
+        let rows = self.spans(-y, h, sheet_rows[0].1, sheet_rows[2].1);
+        let (trfs, uvs) = sprites.get_sprite_range_mut(group, start..start + Self::SPRITE_COUNT);
+        for (row, ((sy, sh), (cy, ch))) in sheet_rows.into_iter().zip(rows).enumerate() {
+            for (col, ((sx, sw), (cx, cw))) in sheet_cols.into_iter().zip(cols).enumerate() {
+                let i = row * 3 + col;
+                // Rows were laid out downward from -y
+                trfs[i] = Transform::new([cx, -cy], [cw, ch], 0.0);
+                uvs[i] = SheetRegion::new(region.sheet, sx, sy, region.depth, sw, sh)
+                    .flip_x(region.is_flipped_x())
+                    .flip_y(region.is_flipped_y());
+            }
+        }
+        Self::SPRITE_COUNT
+    }
+    /// The center and length of each of the three slices of a span
+    /// of `length` starting at `start`, with borders of `first` and
+    /// `last` sheet pixels.
+    fn spans(&self, start: f32, length: f32, first: u16, last: u16) -> [(f32, f32); 3] {
+        let first = f32::from(first) * self.border_scale;
+        let last = f32::from(last) * self.border_scale;
+        let fit = if first + last > length {
+            length / (first + last)
+        } else {
+            1.0
+        };
+        let (first, last) = (first * fit, last * fit);
+        let middle = length - first - last;
+        [
+            (start + first / 2.0, first),
+            (start + first + middle / 2.0, middle),
+            (start + length - last / 2.0, last),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NineSlice;
+    use crate::SheetRegion;
+
+    fn panel() -> NineSlice {
+        NineSlice::with_sheet_region(SheetRegion::new(0, 0, 0, 0, 32, 32), [4, 6, 5, 5])
+            .with_border_scale(2.0)
+    }
+
+    #[test]
+    fn borders_keep_their_size_and_the_middle_stretches() {
+        // 4 and 6 pixel borders at scale 2 are 8 and 12 units wide
+        assert_eq!(
+            panel().spans(10.0, 100.0, 4, 6),
+            [(14.0, 8.0), (58.0, 80.0), (104.0, 12.0)]
+        );
+    }
+
+    #[test]
+    fn borders_shrink_to_fit_a_small_panel() {
+        // Half the room the borders want, so they're half as wide
+        assert_eq!(
+            panel().spans(10.0, 10.0, 4, 6),
+            [(12.0, 4.0), (14.0, 0.0), (17.0, 6.0)]
+        );
+    }
+
+    #[test]
+    fn borders_exactly_filling_the_panel_leave_no_middle() {
+        assert_eq!(
+            panel().spans(0.0, 20.0, 4, 6),
+            [(4.0, 8.0), (8.0, 0.0), (14.0, 12.0)]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "larger than the sheet region")]
+    fn overlapping_borders_are_rejected() {
+        NineSlice::with_sheet_region(SheetRegion::new(0, 0, 0, 0, 8, 8), [4, 5, 0, 0]);
+    }
+}
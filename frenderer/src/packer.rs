@@ -0,0 +1,262 @@
+//! Packs many images of different sizes into the layers of a single
+//! array texture at runtime, so they can all be drawn from one sprite
+//! group.
+
+use crate::{SheetRegion, WGPU};
+use image::RgbaImage;
+
+/// An error encountered while packing images into a texture atlas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PackError {
+    /// The image with the given index (in the order images were
+    /// added) is larger than a single layer of the atlas.
+    TooLarge {
+        index: usize,
+        size: (u32, u32),
+        layer_size: (u32, u32),
+    },
+    /// The images needed more layers than the GPU supports in one array texture.
+    TooManyLayers { layers: u32, max_layers: u32 },
+    /// Packed images are 8-bit RGBA, so the texture must be
+    /// [`wgpu::TextureFormat::Rgba8Unorm`] or
+    /// [`wgpu::TextureFormat::Rgba8UnormSrgb`].
+    UnsupportedFormat(wgpu::TextureFormat),
+}
+
+impl std::fmt::Display for PackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackError::TooLarge {
+                index,
+                size,
+                layer_size,
+            } => write!(
+                f,
+                "image {index} is {}x{}, which doesn't fit in a {}x{} atlas layer",
+                size.0, size.1, layer_size.0, layer_size.1
+            ),
+            PackError::TooManyLayers { layers, max_layers } => write!(
+                f,
+                "packing needs {layers} layers but at most {max_layers} are supported"
+            ),
+            PackError::UnsupportedFormat(format) => {
+                write!(f, "can't pack RGBA images into a {format:?} texture")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PackError {}
+
+/// Collects images to be packed into an array texture.  Images are
+/// placed on horizontal shelves, tallest first, starting a new layer
+/// whenever one fills up.
+pub struct AtlasBuilder<'img> {
+    layer_size: (u32, u32),
+    padding: u32,
+    mipmaps: bool,
+    images: Vec<&'img RgbaImage>,
+}
+
+/// The result of [`AtlasBuilder::build`]: an array texture along with
+/// where each input image ended up in it.
+pub struct PackedAtlas {
+    /// The packed array texture, suitable for
+    /// [`crate::SpriteRenderer::add_sprite_group`].
+    pub texture: wgpu::Texture,
+    /// One region per image, in the order the images were added.
+    pub regions: Vec<SheetRegion>,
+}
+
+impl<'img> AtlasBuilder<'img> {
+    /// Create a builder whose layers will each be `width` by `height` pixels.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            layer_size: (width, height),
+            padding: 1,
+            mipmaps: false,
+            images: vec![],
+        }
+    }
+    /// Set how many transparent pixels separate packed images (1 by
+    /// default), which keeps linear filtering from bleeding neighbors
+    /// into each other.
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+    /// Generate mipmaps for the packed texture (see
+    /// [`WGPU::create_array_texture_with_mipmaps`]).  Consider more
+    /// padding when using mipmaps.
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+    /// Add an image to be packed, returning the index of its region
+    /// in [`PackedAtlas::regions`].
+    pub fn add(&mut self, image: &'img RgbaImage) -> usize {
+        self.images.push(image);
+        self.images.len() - 1
+    }
+    /// Work out where each image goes, returning the number of layers
+    /// needed and a region for each image.
+    pub fn pack(&self) -> Result<(u32, Vec<SheetRegion>), PackError> {
+        let (layer_w, layer_h) = self.layer_size;
+        let mut order: Vec<usize> = (0..self.images.len()).collect();
+        // Tallest first, so each shelf is about as tall as what's on it
+        order.sort_by_key(|&i| std::cmp::Reverse(self.images[i].height()));
+        let mut regions = vec![SheetRegion::default(); self.images.len()];
+        let (mut layer, mut x, mut y, mut shelf_h) = (0_u32, 0_u32, 0_u32, 0_u32);
+        for index in order {
+            let (w, h) = self.images[index].dimensions();
+            if w > layer_w || h > layer_h || w > u16::MAX as u32 || h > u16::MAX as u32 {
+                return Err(PackError::TooLarge {
+                    index,
+                    size: (w, h),
+                    layer_size: self.layer_size,
+                });
+            }
+            if x + w > layer_w {
+                // next shelf
+                x = 0;
+                y += shelf_h + self.padding;
+                shelf_h = 0;
+            }
+            if y + h > layer_h {
+                // next layer
+                layer += 1;
+                x = 0;
+                y = 0;
+                shelf_h = 0;
+            }
+            regions[index] =
+                SheetRegion::new(layer as u16, x as u16, y as u16, 0, w as u16, h as u16);
+            x += w + self.padding;
+            shelf_h = shelf_h.max(h);
+        }
+        let layers = if self.images.is_empty() { 1 } else { layer + 1 };
+        Ok((layers, regions))
+    }
+    /// Pack the images and upload them into a new array texture.
+    /// `format` must be [`wgpu::TextureFormat::Rgba8UnormSrgb`] or
+    /// [`wgpu::TextureFormat::Rgba8Unorm`], depending on whether the
+    /// images are in sRGB or linear color.
+    pub fn build(
+        &self,
+        gpu: &WGPU,
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+    ) -> Result<PackedAtlas, PackError> {
+        if !matches!(
+            format,
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb
+        ) {
+            return Err(PackError::UnsupportedFormat(format));
+        }
+        let (layers, regions) = self.pack()?;
+        let max_layers = gpu.device.limits().max_texture_array_layers;
+        if layers > max_layers {
+            return Err(PackError::TooManyLayers { layers, max_layers });
+        }
+        let (layer_w, layer_h) = self.layer_size;
+        let mut canvases: Vec<RgbaImage> = (0..layers)
+            .map(|_| RgbaImage::new(layer_w, layer_h))
+            .collect();
+        for (image, region) in self.images.iter().zip(regions.iter()) {
+            image::imageops::replace(
+                &mut canvases[region.sheet as usize],
+                *image,
+                region.x as i64,
+                region.y as i64,
+            );
+        }
+        let layer_data: Vec<&[u8]> = canvases.iter().map(|c| c.as_raw().as_slice()).collect();
+        let texture = if self.mipmaps {
+            gpu.create_array_texture_with_mipmaps(&layer_data, format, self.layer_size, label)
+        } else {
+            gpu.create_array_texture(&layer_data, format, self.layer_size, label)
+        };
+        Ok(PackedAtlas { texture, regions })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AtlasBuilder, PackError};
+    use image::RgbaImage;
+
+    #[test]
+    fn full_shelf_starts_a_new_one() {
+        let img = RgbaImage::new(30, 20);
+        let mut builder = AtlasBuilder::new(64, 64);
+        for _ in 0..3 {
+            builder.add(&img);
+        }
+        let (layers, regions) = builder.pack().unwrap();
+        assert_eq!(layers, 1);
+        assert_eq!(regions[0].sheet, 0);
+        assert_eq!((regions[0].x, regions[0].y), (0, 0));
+        assert_eq!((regions[0].w, regions[0].h), (30, 20));
+        assert_eq!(regions[1].sheet, 0);
+        assert_eq!((regions[1].x, regions[1].y), (31, 0));
+        // The third doesn't fit beside the first two, so it goes below
+        // them, past a row of padding
+        assert_eq!(regions[2].sheet, 0);
+        assert_eq!((regions[2].x, regions[2].y), (0, 21));
+        assert_eq!((regions[2].w, regions[2].h), (30, 20));
+    }
+
+    #[test]
+    fn tallest_images_go_first() {
+        let short = RgbaImage::new(10, 5);
+        let tall = RgbaImage::new(10, 30);
+        let mut builder = AtlasBuilder::new(64, 64).padding(0);
+        builder.add(&short);
+        builder.add(&tall);
+        let (_, regions) = builder.pack().unwrap();
+        assert_eq!((regions[1].x, regions[1].y), (0, 0));
+        assert_eq!((regions[1].w, regions[1].h), (10, 30));
+        assert_eq!((regions[0].x, regions[0].y), (10, 0));
+        assert_eq!((regions[0].w, regions[0].h), (10, 5));
+    }
+
+    #[test]
+    fn full_layer_starts_a_new_one() {
+        let img = RgbaImage::new(32, 20);
+        let mut builder = AtlasBuilder::new(32, 32).padding(0);
+        builder.add(&img);
+        builder.add(&img);
+        builder.add(&img);
+        let (layers, regions) = builder.pack().unwrap();
+        assert_eq!(layers, 3);
+        for (layer, region) in regions.into_iter().enumerate() {
+            assert_eq!(region.sheet, layer as u16);
+            assert_eq!((region.x, region.y), (0, 0));
+            assert_eq!((region.w, region.h), (32, 20));
+        }
+    }
+
+    #[test]
+    fn no_images_still_needs_a_layer() {
+        let (layers, regions) = AtlasBuilder::new(16, 16).pack().unwrap();
+        assert_eq!(layers, 1);
+        assert!(regions.is_empty());
+    }
+
+    #[test]
+    fn images_bigger_than_a_layer_are_rejected() {
+        let small = RgbaImage::new(8, 8);
+        let wide = RgbaImage::new(40, 8);
+        let mut builder = AtlasBuilder::new(32, 32);
+        builder.add(&small);
+        builder.add(&wide);
+        assert_eq!(
+            builder.pack().unwrap_err(),
+            PackError::TooLarge {
+                index: 1,
+                size: (40, 8),
+                layer_size: (32, 32),
+            }
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! Fullscreen effects applied to the whole frame after sprites are
+//! drawn.  Set them up with [`crate::Renderer::set_post_effects`];
+//! the scene is then drawn into an offscreen texture, and each effect
+//! in turn reads the previous result and draws the next, the last one
+//! drawing into the window.
+
+use std::borrow::Cow;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::WGPU;
+
+/// A post-processing effect.  Colors are linear RGB in `0.0..=1.0`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PostEffect {
+    /// Fade toward `color` near the edges of the screen.  The fade
+    /// begins `radius` of the way from the center to the corners and
+    /// reaches `strength` (`0.0..=1.0`) `softness` further out.
+    Vignette {
+        color: [f32; 3],
+        strength: f32,
+        radius: f32,
+        softness: f32,
+    },
+    /// Darken every `spacing`-th row of window pixels by up to
+    /// `intensity` (`0.0..=1.0`), like an old CRT.
+    Scanlines { intensity: f32, spacing: f32 },
+    /// Adjust colors: multiply by `tint`, then scale saturation
+    /// around gray (0.0 is grayscale), contrast around middle gray
+    /// and finally add `brightness`.  `1.0` for each multiplier and
+    /// `0.0` brightness leaves colors unchanged.
+    Grade {
+        brightness: f32,
+        contrast: f32,
+        saturation: f32,
+        tint: [f32; 3],
+    },
+    /// Gaussian blur reaching about `radius` window pixels out.
+    /// This takes two passes.
+    Blur { radius: f32 },
+}
+
+impl PostEffect {
+    /// A black vignette of the given strength covering the outer
+    /// part of the screen.
+    pub fn vignette(strength: f32) -> Self {
+        PostEffect::Vignette {
+            color: [0.0; 3],
+            strength,
+            radius: 0.5,
+            softness: 0.5,
+        }
+    }
+}
+
+/// The uniform data for one pass; must match `Params` in the shader.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct PassParams {
+    a: [f32; 4],
+    b: [f32; 4],
+    size: [f32; 4],
+}
+
+#[derive(Clone, Copy)]
+enum PassKind {
+    Vignette,
+    Scanlines,
+    Grade,
+    Blur,
+}
+
+/// The fragment shader for each [`PassKind`], in the same order.
+const ENTRY_POINTS: [&str; 4] = ["fs_vignette", "fs_scanlines", "fs_grade", "fs_blur"];
+
+struct Pass {
+    kind: PassKind,
+    params: PassParams,
+    buffer: wgpu::Buffer,
+}
+
+/// An offscreen color target and a bind group for reading it.
+struct Target {
+    view: wgpu::TextureView,
+    bind_groups: Vec<wgpu::BindGroup>,
+}
+
+pub(crate) struct PostProcessor {
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipelines: Vec<wgpu::RenderPipeline>,
+    effects: Vec<PostEffect>,
+    passes: Vec<Pass>,
+    // Passes alternate between reading one of these and writing the other.
+    targets: Vec<Target>,
+}
+
+impl PostProcessor {
+    pub(crate) fn new(gpu: &WGPU) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("postprocess"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("postprocess.wgsl"))),
+            });
+        let bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("postprocess"),
+                    entries: &[
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 0,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                multisampled: false,
+                                view_dimension: wgpu::TextureViewDimension::D2,
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 1,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("postprocess"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipelines = ENTRY_POINTS
+            .iter()
+            .map(|entry_point| {
+                gpu.device
+                    .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                        label: Some(entry_point),
+                        layout: Some(&pipeline_layout),
+                        vertex: wgpu::VertexState {
+                            module: &shader,
+                            entry_point: "vs_main",
+                            buffers: &[],
+                        },
+                        fragment: Some(wgpu::FragmentState {
+                            module: &shader,
+                            entry_point,
+                            targets: &[Some(gpu.config.format.into())],
+                        }),
+                        primitive: wgpu::PrimitiveState::default(),
+                        depth_stencil: None,
+                        multisample: wgpu::MultisampleState::default(),
+                        multiview: None,
+                    })
+            })
+            .collect();
+        let sampler = gpu.device.create_sampler(&crate::sampler_descriptor(
+            wgpu::FilterMode::Linear,
+            wgpu::AddressMode::ClampToEdge,
+        ));
+        Self {
+            bind_group_layout,
+            sampler,
+            pipelines,
+            effects: vec![],
+            passes: vec![],
+            targets: vec![],
+        }
+    }
+    pub(crate) fn effects(&self) -> &[PostEffect] {
+        &self.effects
+    }
+    pub(crate) fn set_effects(&mut self, gpu: &WGPU, effects: &[PostEffect]) {
+        self.effects = effects.to_vec();
+        self.passes = effects
+            .iter()
+            .flat_map(|effect| match *effect {
+                PostEffect::Vignette {
+                    color: [r, g, b],
+                    strength,
+                    radius,
+                    softness,
+                } => vec![(
+                    PassKind::Vignette,
+                    [r, g, b, strength],
+                    [radius, softness.max(0.0001), 0.0, 0.0],
+                )],
+                PostEffect::Scanlines { intensity, spacing } => vec![(
+                    PassKind::Scanlines,
+                    [intensity, spacing, 0.0, 0.0],
+                    [0.0; 4],
+                )],
+                PostEffect::Grade {
+                    brightness,
+                    contrast,
+                    saturation,
+                    tint: [r, g, b],
+                } => vec![(
+                    PassKind::Grade,
+                    [brightness, contrast, saturation, 0.0],
+                    [r, g, b, 0.0],
+                )],
+                PostEffect::Blur { radius } => {
+                    // The 9 taps span four steps on each side
+                    let step = radius / 4.0;
+                    vec![
+                        (PassKind::Blur, [step, 0.0, 0.0, 0.0], [0.0; 4]),
+                        (PassKind::Blur, [0.0, step, 0.0, 0.0], [0.0; 4]),
+                    ]
+                }
+            })
+            .map(|(kind, a, b)| Pass {
+                kind,
+                params: PassParams {
+                    a,
+                    b,
+                    size: [0.0; 4],
+                },
+                buffer: gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("postprocess params"),
+                    size: std::mem::size_of::<PassParams>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+            })
+            .collect();
+        self.resize(gpu);
+    }
+    /// Recreate the offscreen targets to match the window size.
+    pub(crate) fn resize(&mut self, gpu: &WGPU) {
+        if self.passes.is_empty() {
+            self.targets.clear();
+            return;
+        }
+        let (width, height) = (gpu.config.width, gpu.config.height);
+        let size = [
+            width as f32,
+            height as f32,
+            1.0 / width as f32,
+            1.0 / height as f32,
+        ];
+        for pass in self.passes.iter_mut() {
+            pass.params.size = size;
+            gpu.queue
+                .write_buffer(&pass.buffer, 0, bytemuck::bytes_of(&pass.params));
+        }
+        // Only one target is needed if there's just one pass
+        let target_count = self.passes.len().min(2);
+        self.targets = (0..target_count)
+            .map(|_| {
+                let view = gpu.create_frame_target("postprocess target");
+                let bind_groups = self
+                    .passes
+                    .iter()
+                    .map(|pass| {
+                        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                            label: None,
+                            layout: &self.bind_group_layout,
+                            entries: &[
+                                wgpu::BindGroupEntry {
+                                    binding: 0,
+                                    resource: wgpu::BindingResource::TextureView(&view),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 1,
+                                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                                },
+                                wgpu::BindGroupEntry {
+                                    binding: 2,
+                                    resource: pass.buffer.as_entire_binding(),
+                                },
+                            ],
+                        })
+                    })
+                    .collect();
+                Target { view, bind_groups }
+            })
+            .collect();
+    }
+    /// Where the scene should be drawn, or `None` if there are no
+    /// effects and it should be drawn straight to the screen.
+    pub(crate) fn scene_target(&self) -> Option<&wgpu::TextureView> {
+        self.targets.first().map(|target| &target.view)
+    }
+    /// Run every pass, starting from the scene drawn into
+    /// [`PostProcessor::scene_target`] and ending in `view`.
+    pub(crate) fn apply(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        for (i, pass) in self.passes.iter().enumerate() {
+            let source = &self.targets[i % 2];
+            let dest = if i + 1 == self.passes.len() {
+                view
+            } else {
+                &self.targets[(i + 1) % 2].view
+            };
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("postprocess"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: dest,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.pipelines[pass.kind as usize]);
+            rpass.set_bind_group(0, &source.bind_groups[i], &[]);
+            rpass.draw(0..3, 0..1);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// Fullscreen post-processing effects.  Each fragment entry point reads
+// the previous pass's output and writes the next one.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+struct Params {
+    // meaning depends on the effect; see PostEffect
+    a: vec4<f32>,
+    b: vec4<f32>,
+    // target width, height, 1/width, 1/height
+    size: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var t_src: texture_2d<f32>;
+@group(0) @binding(1)
+var s_src: sampler;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // (0,0), (2,0), (0,2) in texture space
+    let uv = vec2(f32((in_vertex_index << 1u) & 2u), f32(in_vertex_index & 2u));
+    let pos = vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return VertexOutput(pos, uv);
+}
+
+// a: color.rgb, strength; b: radius, softness
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_src, s_src, in.tex_coords);
+    // 0 at the center and 1 in the corners, keeping the falloff round
+    let aspect = vec2(params.size.x * params.size.w, 1.0);
+    let dist = length((in.tex_coords - 0.5) * aspect) / length(0.5 * aspect);
+    let amount = smoothstep(params.b.x, params.b.x + params.b.y, dist) * params.a.w;
+    return vec4(mix(color.rgb, params.a.rgb, amount), color.a);
+}
+
+// a: intensity, spacing in pixels
+@fragment
+fn fs_scanlines(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_src, s_src, in.tex_coords);
+    let y = floor(in.tex_coords.y * params.size.y);
+    let line = 0.5 + 0.5 * cos(6.2831853 * y / max(params.a.y, 1.0));
+    return vec4(color.rgb * (1.0 - params.a.x * line), color.a);
+}
+
+// a: brightness, contrast, saturation; b: tint.rgb
+@fragment
+fn fs_grade(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_src, s_src, in.tex_coords);
+    var rgb = color.rgb * params.b.rgb;
+    let luma = dot(rgb, vec3(0.2126, 0.7152, 0.0722));
+    rgb = mix(vec3(luma), rgb, params.a.z);
+    rgb = (rgb - 0.5) * params.a.y + 0.5 + params.a.x;
+    return vec4(clamp(rgb, vec3(0.0), vec3(1.0)), color.a);
+}
+
+// a: step between taps in pixels (x, y); blurring takes one
+// horizontal and one vertical pass.
+@fragment
+fn fs_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    let step = params.a.xy * params.size.zw;
+    // 9-tap Gaussian
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    var sum = textureSample(t_src, s_src, in.tex_coords) * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = step * f32(i);
+        sum += textureSample(t_src, s_src, in.tex_coords + offset) * weights[i];
+        sum += textureSample(t_src, s_src, in.tex_coords - offset) * weights[i];
+    }
+    return sum;
+}
@@ -12,38 +12,54 @@ var<private> VERTICES:array<vec2<f32>,6> = array<vec2<f32>,6>(
 struct Camera {
     screen_pos: vec2<f32>,
     screen_size: vec2<f32>,
+    focus: vec2<f32>,
+    rotation: f32,
+    zoom: f32,
+}
+
+struct TransformData {
+    // width and height as two half-precision floats
+    size:u32,
+    x:f32,
+    y:f32,
+    rot:f32
 }
 
 struct UVData {
     sheet_depth:u32,
     xy:u32,
     wh:u32,
-    _padding:u32
+    flags:u32
 }
 
+// These must match the flag bits in SheetRegion.
+const FLIP_X:u32 = 1u;
+const FLIP_Y:u32 = 2u;
+const ROTATED:u32 = 4u;
+
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 @group(0) @binding(1)
-var<storage, read> s_world: array<vec4<f32>>;
+var<storage, read> s_world: array<TransformData>;
 @group(0) @binding(2)
 var<storage, read> s_sheet: array<UVData>;
+@group(0) @binding(3)
+var<storage, read> s_color: array<u32>;
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) @interpolate(flat) tex_index: u32,
+    @location(2) @interpolate(flat) tint: vec4<f32>,
 }
 
-fn sprite_to_vert(trf:vec4<f32>, uvs:UVData, norm_vert:vec2<f32>) -> VertexOutput {
-  let center:vec2<f32> = trf.yz;
-  let size_bits:u32 = bitcast<u32>(trf.x);
-  let size:vec2<f32> = vec2(f32(size_bits & 0x0000FFFFu),
-                            f32((size_bits & 0xFFFF0000u) >> 16u)
-                            );
+fn sprite_to_vert(trf:TransformData, uvs:UVData, tint:vec4<f32>, norm_vert:vec2<f32>) -> VertexOutput {
+  let center:vec2<f32> = vec2(trf.x, trf.y);
+  let size:vec2<f32> = unpack2x16float(trf.size);
   let tex_layer = uvs.sheet_depth & 0x0000FFFFu;
   let tex_depth = (uvs.sheet_depth & 0xFFFF0000u) >> 16u;
   let tex_size:vec2<u32> = textureDimensions(t_diffuse);
-  let rot:f32 = trf.w;
+  let rot:f32 = trf.rot;
   let sinrot:f32 = sin(rot);
   let cosrot:f32 = cos(rot);
   // scale
@@ -54,7 +70,15 @@ fn sprite_to_vert(trf:vec4<f32>, uvs:UVData, norm_vert:vec2<f32>) -> VertexOutpu
                      );
   // now translate by trf (center, size):
   let world_pos = (center) + rotated;
-  let camera_pos = world_pos - camera.screen_pos;
+  // zoom and rotate (the opposite way to the camera) around the focus
+  let focus = camera.focus * camera.screen_size;
+  let view_pos = world_pos - camera.screen_pos - focus;
+  let camsin = sin(camera.rotation);
+  let camcos = cos(camera.rotation);
+  let camera_pos = vec2(
+                        view_pos.x*camcos+view_pos.y*camsin,
+                        view_pos.y*camcos-view_pos.x*camsin
+                        ) * camera.zoom + focus;
   let box_pos = camera_pos / (camera.screen_size*0.5);
   let ndc_pos = vec4(box_pos.xy, 0.0, 1.0) - vec4(1.0, 1.0, 0.0, 0.0);
   let tex_x = uvs.xy & 0x0000FFFFu;
@@ -63,9 +87,14 @@ fn sprite_to_vert(trf:vec4<f32>, uvs:UVData, norm_vert:vec2<f32>) -> VertexOutpu
   let tex_h = (uvs.wh & 0xFFFF0000u) >> 16u;
   let tex_corner = vec2(f32(tex_x) / f32(tex_size.x), f32(tex_y) / f32(tex_size.y));
   let tex_uv_size = vec2(f32(tex_w) / f32(tex_size.x), f32(tex_h) / f32(tex_size.y));
-  let norm_uv = vec2(norm_vert.x+0.5, 1.0-(norm_vert.y+0.5));
+  var norm_uv = vec2(norm_vert.x+0.5, 1.0-(norm_vert.y+0.5));
+  if (uvs.flags & FLIP_X) != 0u { norm_uv.x = 1.0 - norm_uv.x; }
+  if (uvs.flags & FLIP_Y) != 0u { norm_uv.y = 1.0 - norm_uv.y; }
+  // Rotated regions are stored turned 90 degrees clockwise, so the
+  // upright sprite's top left corner is the region's top right.
+  if (uvs.flags & ROTATED) != 0u { norm_uv = vec2(1.0 - norm_uv.y, norm_uv.x); }
   // Larger y = smaller depth = closer to screen
-  return VertexOutput(ndc_pos+vec4(0.0, 0.0, f32(tex_depth)/65535.0, 0.0), tex_corner + norm_uv*tex_uv_size, tex_layer);
+  return VertexOutput(ndc_pos+vec4(0.0, 0.0, f32(tex_depth)/65535.0, 0.0), tex_corner + norm_uv*tex_uv_size, tex_layer, tint);
 }
 
 @vertex
@@ -73,7 +102,8 @@ fn vs_storage_main(@builtin(vertex_index) in_vertex_index: u32, @builtin(instanc
   // We'll just look up the vertex data in those constant arrays
   let trf = s_world[sprite_index];
   let uvs = s_sheet[sprite_index];
-  return sprite_to_vert(trf, uvs, VERTICES[in_vertex_index]);
+  let tint = unpack4x8unorm(s_color[sprite_index]);
+  return sprite_to_vert(trf, uvs, tint, VERTICES[in_vertex_index]);
 }
 
 @vertex
@@ -82,12 +112,14 @@ fn vs_storage_noinstance_main(@builtin(vertex_index) in_vertex_index: u32) -> Ve
     let vertex_index:u32 = in_vertex_index - (sprite_index * u32(6));
     let trf = s_world[sprite_index];
     let uvs = s_sheet[sprite_index];
-    return sprite_to_vert(trf, uvs, VERTICES[in_vertex_index]);
+    let tint = unpack4x8unorm(s_color[sprite_index]);
+    return sprite_to_vert(trf, uvs, tint, VERTICES[in_vertex_index]);
 }
 
 @vertex
-fn vs_vbuf_main(@builtin(vertex_index) in_vertex_index: u32, @location(0) trf:vec4<f32>, @location(1) sheet_region:vec4<u32>) -> VertexOutput {
-  return sprite_to_vert(trf, UVData(sheet_region.x, sheet_region.y, sheet_region.z, sheet_region.w), VERTICES[in_vertex_index]);
+fn vs_vbuf_main(@builtin(vertex_index) in_vertex_index: u32, @location(0) trf_size:u32, @location(3) trf_xyrot:vec3<f32>, @location(1) sheet_region:vec4<u32>, @location(2) tint:vec4<f32>) -> VertexOutput {
+  let trf = TransformData(trf_size, trf_xyrot.x, trf_xyrot.y, trf_xyrot.z);
+  return sprite_to_vert(trf, UVData(sheet_region.x, sheet_region.y, sheet_region.z, sheet_region.w), tint, VERTICES[in_vertex_index]);
 }
 
 
@@ -104,7 +136,15 @@ var s_diffuse: sampler;
 @fragment
 fn fs_main(in:VertexOutput) -> @location(0) vec4<f32> {
     // And we use the tex coords from the vertex output to sample from the texture.
-    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index);
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index) * in.tint;
     if color.w < 0.2 { discard; }
     return color;
 }
+
+// Translucent sprites keep their alpha for blending instead of being cut out.
+@fragment
+fn fs_blend_main(in:VertexOutput) -> @location(0) vec4<f32> {
+    let color:vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords, in.tex_index) * in.tint;
+    if color.w <= 0.0 { discard; }
+    return color;
+}
@@ -1,10 +1,11 @@
 //! A sprite renderer with multiple layers ("sprite groups") which can
 //! be independently transformed.
 
-use std::{borrow::Cow, ops::Range};
+use std::{borrow::Cow, cell::Cell, ops::Range};
 
 use crate::{USE_STORAGE, WGPU};
 use bytemuck::{Pod, Zeroable};
+use half::f16;
 
 /// A SheetRegion defines the visual appearance of a sprite: which spritesheet (of an array of spritesheets), its pixel region within the spritesheet, and its visual depth (larger meaning further away).
 #[repr(C)]
@@ -22,7 +23,8 @@ pub struct SheetRegion {
     pub w: u16,
     /// The height in pixels of this sprite within the spritesheet texture.
     pub h: u16,
-    _padding_32: u32,
+    /// Bit flags controlling how the region is mapped onto the sprite; see [`SheetRegion::flip_x`], [`SheetRegion::flip_y`], and [`SheetRegion::rotated`].
+    flags: u32,
 }
 
 impl SheetRegion {
@@ -35,7 +37,7 @@ impl SheetRegion {
             w,
             h,
             depth,
-            _padding_32: 0,
+            flags: 0,
         }
     }
     /// Create a simple [`SheetRegion`] with just the rectangle coordinates ([`SheetRegion::sheet`] and [`SheetRegion::depth`] will be set to 0).
@@ -53,21 +55,111 @@ impl SheetRegion {
     pub const fn depth(self, depth: u16) -> Self {
         Self { depth, ..self }
     }
+    /// Produce a new [`SheetRegion`] which is (or isn't) mirrored horizontally.
+    pub const fn flip_x(self, flip: bool) -> Self {
+        self.with_flag(Self::FLIP_X, flip)
+    }
+    /// Produce a new [`SheetRegion`] which is (or isn't) mirrored vertically.
+    pub const fn flip_y(self, flip: bool) -> Self {
+        self.with_flag(Self::FLIP_Y, flip)
+    }
+    /// Produce a new [`SheetRegion`] whose pixels are (or aren't)
+    /// stored rotated 90 degrees clockwise in the spritesheet, as
+    /// texture packers do to fit sprites more tightly.  `x`, `y`, `w`,
+    /// and `h` still describe the rectangle as it sits in the sheet,
+    /// so a rotated region's upright width is its `h`.  Rotated regions
+    /// are drawn upright; flips apply to the upright sprite.
+    pub const fn rotated(self, rotated: bool) -> Self {
+        self.with_flag(Self::ROTATED, rotated)
+    }
+    /// Whether this region is stored rotated in the spritesheet.
+    pub const fn is_rotated(&self) -> bool {
+        self.flags & Self::ROTATED != 0
+    }
+    /// Whether this region is mirrored horizontally.
+    pub const fn is_flipped_x(&self) -> bool {
+        self.flags & Self::FLIP_X != 0
+    }
+    /// Whether this region is mirrored vertically.
+    pub const fn is_flipped_y(&self) -> bool {
+        self.flags & Self::FLIP_Y != 0
+    }
+    // These must match the flag bits in the shader.
+    const FLIP_X: u32 = 1 << 0;
+    const FLIP_Y: u32 = 1 << 1;
+    const ROTATED: u32 = 1 << 2;
+    const fn with_flag(self, flag: u32, on: bool) -> Self {
+        Self {
+            flags: if on {
+                self.flags | flag
+            } else {
+                self.flags & !flag
+            },
+            ..self
+        }
+    }
+}
+
+/// A Color is a per-sprite RGBA multiplier (a tint) applied to every
+/// texel the sprite samples.  [`Color::WHITE`] leaves sprites as
+/// they are.  Note that reducing alpha only makes sprites
+/// translucent in alpha blended groups; cutout groups
+/// discard any pixel whose tinted alpha falls below 0.2.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod, Debug, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    /// No tint at all.
+    pub const WHITE: Self = Self::rgba(255, 255, 255, 255);
+    /// Draws a sprite's silhouette in black.
+    pub const BLACK: Self = Self::rgba(0, 0, 0, 255);
+    /// Makes a sprite invisible.
+    pub const TRANSPARENT: Self = Self::rgba(0, 0, 0, 0);
+    /// Create a new [`Color`] from its components.
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+    /// Create a new opaque [`Color`].
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::rgba(r, g, b, 255)
+    }
+    /// Produce a new [`Color`] with a different alpha.
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self { a, ..self }
+    }
+    /// Produce a new [`Color`] with its alpha scaled by `opacity` (clamped to `0.0..=1.0`).
+    pub fn fade(self, opacity: f32) -> Self {
+        self.with_alpha((self.a as f32 * opacity.clamp(0.0, 1.0)).round() as u8)
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::WHITE
+    }
 }
 
 /// A Transform describes a location, an extent, and a rotation in 2D
-/// space.  Width and height are crammed into 4 bytes meaning the
-/// maximum width and height are [`u16::MAX`] and fractional widths
-/// and heights are not supported.  The location `(x,y)` is typically
+/// space.  Width and height are crammed into 4 bytes as
+/// half-precision floats, so they keep about three significant
+/// digits and can be at most 65504; use [`Transform::new`] or
+/// [`Transform::with_size`] to set them from `f32`s.  Different widths and
+/// heights give non-uniform scaling.  The location `(x,y)` is typically
 /// interpreted as the center of the object after translation.
 /// Rotations are in radians, counterclockwise about the center point.
 #[repr(C)]
 #[derive(Clone, Copy, Zeroable, Pod, Debug)]
 pub struct Transform {
     /// The horizontal scale of the transform
-    pub w: u16,
+    pub w: f16,
     /// The vertical scale of the transform
-    pub h: u16,
+    pub h: f16,
     /// The x coordinate of the translation
     pub x: f32,
     /// The y coordinate of the translation
@@ -77,13 +169,36 @@ pub struct Transform {
 }
 
 impl Transform {
+    /// Create a new [`Transform`] centered at `(x,y)` with the given size and rotation.
+    pub fn new([x, y]: [f32; 2], [w, h]: [f32; 2], rot: f32) -> Self {
+        Self {
+            w: f16::from_f32(w),
+            h: f16::from_f32(h),
+            x,
+            y,
+            rot,
+        }
+    }
     pub fn translation(&self) -> [f32; 2] {
         [self.x, self.y]
     }
+    /// The width and height of this transform.
+    pub fn size(&self) -> [f32; 2] {
+        [self.w.to_f32(), self.h.to_f32()]
+    }
+    /// Produce a new [`Transform`] with a different size.
+    pub fn with_size(self, [w, h]: [f32; 2]) -> Self {
+        Self {
+            w: f16::from_f32(w),
+            h: f16::from_f32(h),
+            ..self
+        }
+    }
 }
 
 /// GPUCamera is a transform for a sprite layer, defining a scale
-/// followed by a translation.
+/// followed by a translation, with optional zoom and rotation around
+/// a focus point within the viewport.
 #[repr(C)]
 #[derive(Clone, Copy, Zeroable, Pod, Debug)]
 pub struct GPUCamera {
@@ -91,29 +206,269 @@ pub struct GPUCamera {
     pub screen_pos: [f32; 2],
     /// The size of the camera viewport in world space pixels
     pub screen_size: [f32; 2],
+    /// The point the camera zooms and rotates around, as a fraction
+    /// of the viewport from its bottom left corner; `[0.5, 0.5]` is
+    /// the center of the screen.
+    pub focus: [f32; 2],
+    /// How far the camera is turned counterclockwise, in radians.
+    /// The world appears to turn the other way.
+    pub rotation: f32,
+    /// How much the view is magnified; 2.0 makes everything look
+    /// twice as big.
+    pub zoom: f32,
+}
+
+impl GPUCamera {
+    /// A camera showing the `screen_size` world space pixels whose
+    /// bottom left corner is at `screen_pos`, without zoom or
+    /// rotation.
+    pub fn new(screen_pos: [f32; 2], screen_size: [f32; 2]) -> Self {
+        Self {
+            screen_pos,
+            screen_size,
+            focus: [0.5, 0.5],
+            rotation: 0.0,
+            zoom: 1.0,
+        }
+    }
+    /// Convert a position in window pixels (measured from the top
+    /// left, like [`crate::input::Input::mouse_pos`]) in a window of
+    /// size `window_size` to world coordinates.
+    pub fn screen_to_world(&self, [sx, sy]: [f32; 2], [ww, wh]: [f32; 2]) -> [f32; 2] {
+        let [fx, fy] = self.focus_offset();
+        let vx = sx / ww * self.screen_size[0] - fx;
+        let vy = (1.0 - sy / wh) * self.screen_size[1] - fy;
+        let (sin, cos) = self.rotation.sin_cos();
+        [
+            (vx * cos - vy * sin) / self.zoom + fx + self.screen_pos[0],
+            (vx * sin + vy * cos) / self.zoom + fy + self.screen_pos[1],
+        ]
+    }
+    /// Convert a world position to window pixels (measured from the
+    /// top left) in a window of size `window_size`; the inverse of
+    /// [`GPUCamera::screen_to_world`].
+    pub fn world_to_screen(&self, [x, y]: [f32; 2], [ww, wh]: [f32; 2]) -> [f32; 2] {
+        let [fx, fy] = self.focus_offset();
+        let px = x - self.screen_pos[0] - fx;
+        let py = y - self.screen_pos[1] - fy;
+        let (sin, cos) = self.rotation.sin_cos();
+        let vx = (px * cos + py * sin) * self.zoom + fx;
+        let vy = (py * cos - px * sin) * self.zoom + fy;
+        [
+            vx / self.screen_size[0] * ww,
+            (1.0 - vy / self.screen_size[1]) * wh,
+        ]
+    }
+    /// The focus point in world space pixels from the viewport's corner.
+    fn focus_offset(&self) -> [f32; 2] {
+        [
+            self.focus[0] * self.screen_size[0],
+            self.focus[1] * self.screen_size[1],
+        ]
+    }
+}
+
+/// How a sprite group's pixels are combined with what's behind them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BlendMode {
+    /// Pixels with alpha below 0.2 are discarded and the rest are
+    /// drawn fully opaque.  Sprites in cutout groups occlude each
+    /// other using the depth buffer, so draw order doesn't matter.
+    #[default]
+    Cutout,
+    /// Pixels are alpha blended over whatever is behind them.  Groups
+    /// using this mode are drawn after all cutout groups, and their
+    /// sprites are sorted back to front by [`SheetRegion::depth`]
+    /// whenever they're uploaded.
+    ///
+    /// Sprites are only sorted within their own group.  Alpha groups
+    /// don't write depth and are drawn one after another in the order
+    /// they were created, so a later group's sprites are drawn over an
+    /// earlier group's even if they're farther away.  Keep
+    /// translucent sprites that overlap each other in the same group.
+    Alpha,
+    /// Pixels are alpha blended just like [`BlendMode::Alpha`], and
+    /// the group is drawn along with the alpha groups, but its
+    /// sprites are drawn in the order they're stored rather than
+    /// sorted by depth.  Only changed sprites are uploaded, which
+    /// suits groups that are rewritten every frame, like particles,
+    /// as long as their owner keeps them back to front.
+    AlphaUnsorted,
+}
+
+/// A handle to a sprite group, returned by
+/// [`SpriteRenderer::add_sprite_group`].  A handle stays valid when
+/// other groups are removed; once its own group is removed, using it
+/// panics (or, for the `try_` accessors, yields `None`), even if a
+/// new group has since taken over the same slot.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SpriteGroupId {
+    index: u32,
+    generation: u32,
+}
+
+impl SpriteGroupId {
+    /// The slot this group occupies; groups are drawn in slot order.
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+/// A handle to a custom fragment shader registered with
+/// [`SpriteRenderer::add_material`].  Materials last as long as the
+/// renderer that created them.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct MaterialId(usize);
+
+/// The pipelines for one material, one per [`BlendMode`].
+struct Material {
+    pipeline: wgpu::RenderPipeline,
+    blend_pipeline: wgpu::RenderPipeline,
+    /// The size in bytes of the material's parameters, padded to a
+    /// multiple of 16.
+    params_size: usize,
+}
+
+/// A slot for a sprite group.  The generation is bumped whenever the
+/// group in the slot is removed, which invalidates old handles.
+struct GroupSlot {
+    generation: u32,
+    group: Option<SpriteGroup>,
 }
 
 struct SpriteGroup {
     world_buffer: wgpu::Buffer,
     sheet_buffer: wgpu::Buffer,
+    color_buffer: wgpu::Buffer,
     world_transforms: Vec<Transform>,
     sheet_regions: Vec<SheetRegion>,
+    colors: Vec<Color>,
     camera: GPUCamera,
     camera_buffer: wgpu::Buffer,
+    tex_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
     tex_bind_group: wgpu::BindGroup,
     sprite_bind_group: wgpu::BindGroup,
+    blend: BlendMode,
+    visible: bool,
+    material: Option<MaterialId>,
+    /// The uniform buffer bound at `@group(2)` for the group's
+    /// material, and its bind group.
+    material_params: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+    /// Sprites changed since they were last uploaded; empty if none.
+    dirty: Cell<Range<usize>>,
+    /// How many groups were created before this one, which orders
+    /// alpha groups regardless of which slots they're in.
+    created: u64,
+}
+
+impl SpriteGroup {
+    /// Upload all of this group's sprites, sorting them back to front
+    /// first if it uses [`BlendMode::Alpha`].
+    fn upload_all(&self, gpu: &WGPU) {
+        self.dirty.set(0..0);
+        if self.blend == BlendMode::Alpha {
+            let mut order: Vec<usize> = (0..self.sheet_regions.len()).collect();
+            // Larger depths are further away.  The sort is stable, so
+            // sprites at the same depth keep their relative order.
+            order.sort_by_key(|&i| std::cmp::Reverse(self.sheet_regions[i].depth));
+            let world_transforms: Vec<Transform> =
+                order.iter().map(|&i| self.world_transforms[i]).collect();
+            let sheet_regions: Vec<SheetRegion> =
+                order.iter().map(|&i| self.sheet_regions[i]).collect();
+            let colors: Vec<Color> = order.iter().map(|&i| self.colors[i]).collect();
+            gpu.queue.write_buffer(
+                &self.world_buffer,
+                0,
+                bytemuck::cast_slice(&world_transforms),
+            );
+            gpu.queue
+                .write_buffer(&self.sheet_buffer, 0, bytemuck::cast_slice(&sheet_regions));
+            gpu.queue
+                .write_buffer(&self.color_buffer, 0, bytemuck::cast_slice(&colors));
+        } else {
+            gpu.queue.write_buffer(
+                &self.world_buffer,
+                0,
+                bytemuck::cast_slice(&self.world_transforms),
+            );
+            gpu.queue.write_buffer(
+                &self.sheet_buffer,
+                0,
+                bytemuck::cast_slice(&self.sheet_regions),
+            );
+            gpu.queue
+                .write_buffer(&self.color_buffer, 0, bytemuck::cast_slice(&self.colors));
+        }
+    }
+    /// Note that the given sprites need to be uploaded before the
+    /// group is next drawn.
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let dirty = self.dirty.take();
+        self.dirty.set(if dirty.is_empty() {
+            range
+        } else {
+            dirty.start.min(range.start)..dirty.end.max(range.end)
+        });
+    }
+    /// Upload the sprites changed since the last upload, if any.
+    fn flush(&self, gpu: &WGPU) {
+        let dirty = self.dirty.take();
+        if dirty.is_empty() {
+            return;
+        }
+        if self.blend == BlendMode::Alpha {
+            self.upload_all(gpu);
+            return;
+        }
+        write_range(
+            gpu,
+            &self.world_buffer,
+            &self.world_transforms,
+            dirty.clone(),
+        );
+        write_range(gpu, &self.sheet_buffer, &self.sheet_regions, dirty.clone());
+        write_range(gpu, &self.color_buffer, &self.colors, dirty);
+    }
+    fn draw<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        if !USE_STORAGE {
+            rpass.set_vertex_buffer(0, self.world_buffer.slice(..));
+            rpass.set_vertex_buffer(1, self.sheet_buffer.slice(..));
+            rpass.set_vertex_buffer(2, self.color_buffer.slice(..));
+        }
+        rpass.set_bind_group(0, &self.sprite_bind_group, &[]);
+        rpass.set_bind_group(1, &self.tex_bind_group, &[]);
+        // draw two triangles per sprite, and sprites-many sprites.
+        // this uses instanced drawing, but it would also be okay
+        // to draw 6 * sprites.len() vertices and use modular arithmetic
+        // to figure out which sprite we're drawing.
+        assert_eq!(self.world_transforms.len(), self.sheet_regions.len());
+        rpass.draw(0..6, 0..self.world_transforms.len() as u32);
+    }
 }
 
 /// SpriteRenderer hosts a number of sprite groups.  Each group has a
 /// specified spritesheet texture array, parallel vectors of
 /// [`Transform`]s and [`SheetRegion`]s, and a [`GPUCamera`] to define
 /// its transform.  Currently, all groups render into the same depth
-/// buffer so their outputs are interleaved.
+/// buffer so their outputs are interleaved.  Groups are identified
+/// by [`SpriteGroupId`]s.
 pub struct SpriteRenderer {
     pipeline: wgpu::RenderPipeline,
+    blend_pipeline: wgpu::RenderPipeline,
     sprite_bind_group_layout: wgpu::BindGroupLayout,
     texture_bind_group_layout: wgpu::BindGroupLayout,
-    groups: Vec<SpriteGroup>,
+    material_bind_group_layout: wgpu::BindGroupLayout,
+    material_pipeline_layout: wgpu::PipelineLayout,
+    materials: Vec<Material>,
+    groups: Vec<GroupSlot>,
+    groups_created: u64,
 }
 
 impl SpriteRenderer {
@@ -210,6 +565,20 @@ impl SpriteRenderer {
                             // No count, not a buffer array binding
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            // This matches the binding in the shader
+                            binding: 3,
+                            // Available in vertex shader
+                            visibility: wgpu::ShaderStages::VERTEX,
+                            // It's a buffer
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            // No count, not a buffer array binding
+                            count: None,
+                        },
                     ],
                 })
         } else {
@@ -227,77 +596,118 @@ impl SpriteRenderer {
                 push_constant_ranges: &[],
             });
 
+        // Materials get one more bind group for their parameters
+        let material_bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("material params"),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                });
+        let material_pipeline_layout =
+            gpu.device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("material"),
+                    bind_group_layouts: &[
+                        &sprite_bind_group_layout,
+                        &texture_bind_group_layout,
+                        &material_bind_group_layout,
+                    ],
+                    push_constant_ranges: &[],
+                });
+
         assert_eq!(std::mem::size_of::<Transform>(), 4 * 4);
         assert_eq!(std::mem::size_of::<SheetRegion>(), 4 * 4);
-        let pipeline = gpu
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: if USE_STORAGE {
-                        "vs_storage_main"
-                    } else {
-                        "vs_vbuf_main"
-                    },
-                    buffers: if USE_STORAGE {
-                        &[]
-                    } else {
-                        &[
-                            wgpu::VertexBufferLayout {
-                                array_stride: std::mem::size_of::<Transform>() as u64,
-                                step_mode: wgpu::VertexStepMode::Instance,
-                                attributes: &[wgpu::VertexAttribute {
-                                    // This is a fun little trick, we
-                                    // lie and say it's four floats.
-                                    // In the shader the first float
-                                    // is cast bitwise to a u32 and
-                                    // then the w and h are masked out
-                                    // and casted back to f32.
-                                    format: wgpu::VertexFormat::Float32x4,
-                                    offset: 0,
-                                    shader_location: 0,
-                                }],
-                            },
-                            wgpu::VertexBufferLayout {
-                                array_stride: std::mem::size_of::<SheetRegion>() as u64,
-                                step_mode: wgpu::VertexStepMode::Instance,
-                                attributes: &[wgpu::VertexAttribute {
-                                    format: wgpu::VertexFormat::Uint32x4,
-                                    offset: 0,
-                                    shader_location: 2,
-                                }],
-                            },
-                        ]
-                    },
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(gpu.config.format.into())],
-                }),
-                primitive: wgpu::PrimitiveState::default(),
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: wgpu::TextureFormat::Depth32Float,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: wgpu::StencilState::default(),
-                    bias: wgpu::DepthBiasState::default(),
-                }),
-                multisample: wgpu::MultisampleState::default(),
-                multiview: None,
-            });
+        let pipeline =
+            create_pipeline(gpu, &pipeline_layout, &shader, "fs_main", BlendMode::Cutout);
+        let blend_pipeline = create_pipeline(
+            gpu,
+            &pipeline_layout,
+            &shader,
+            "fs_blend_main",
+            BlendMode::Alpha,
+        );
 
         Self {
             pipeline,
+            blend_pipeline,
             groups: Vec::default(),
             sprite_bind_group_layout,
             texture_bind_group_layout,
+            material_bind_group_layout,
+            material_pipeline_layout,
+            materials: vec![],
+            groups_created: 0,
         }
     }
+    /// Register a custom fragment shader for sprite groups to use
+    /// instead of the built-in one (see
+    /// [`SpriteRenderer::set_material`]).  `source` is WGSL appended
+    /// to frenderer's own sprite shader, so it can use the
+    /// `VertexOutput` struct (`tex_coords`, `tex_index` and `tint`)
+    /// and sample the group's texture through `t_diffuse` and
+    /// `s_diffuse`.  It must define the entry point:
+    ///
+    /// ```wgsl
+    /// @fragment
+    /// fn fs_material(in: VertexOutput) -> @location(0) vec4<f32>
+    /// ```
+    ///
+    /// It may also declare a uniform at `@group(2) @binding(0)`,
+    /// filled from [`SpriteRenderer::set_material_params`];
+    /// `params_size` is that uniform's size in bytes (usually
+    /// `std::mem::size_of` the Rust struct matching it), or 0 if
+    /// there isn't one.  In
+    /// groups using [`BlendMode::Cutout`] the material should discard
+    /// transparent pixels itself, as nothing is blended.  See
+    /// [`crate::materials`] for some ready-made materials.
+    ///
+    /// Panics if the shader doesn't compile.
+    pub fn add_material(&mut self, gpu: &WGPU, source: &str, params_size: usize) -> MaterialId {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("material"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!(
+                    "{}\n{}",
+                    include_str!("shader.wgsl"),
+                    source
+                ))),
+            });
+        let pipeline = create_pipeline(
+            gpu,
+            &self.material_pipeline_layout,
+            &shader,
+            "fs_material",
+            BlendMode::Cutout,
+        );
+        let blend_pipeline = create_pipeline(
+            gpu,
+            &self.material_pipeline_layout,
+            &shader,
+            "fs_material",
+            BlendMode::Alpha,
+        );
+        self.materials.push(Material {
+            pipeline,
+            blend_pipeline,
+            params_size: material_params_size(params_size),
+        });
+        MaterialId(self.materials.len() - 1)
+    }
     /// Create a new sprite group sized to fit `sprites`.  Returns a
-    /// sprite group identifier (for now, a usize).
+    /// handle to the new sprite group.  New groups reuse the slots of
+    /// removed groups before being added at the end.  If the group
+    /// is alpha blended, it's drawn over every alpha blended group
+    /// created before it.
     pub fn add_sprite_group(
         &mut self,
         gpu: &WGPU,
@@ -305,128 +715,151 @@ impl SpriteRenderer {
         world_transforms: Vec<Transform>,
         sheet_regions: Vec<SheetRegion>,
         camera: GPUCamera,
-    ) -> usize {
-        let view_sprite = tex.create_view(&wgpu::TextureViewDescriptor {
-            dimension: Some(wgpu::TextureViewDimension::D2Array),
-            base_array_layer: 0,
-            array_layer_count: match tex.depth_or_array_layers() {
-                0 => Some(1),
-                layers => Some(layers),
-            },
-            ..Default::default()
-        });
+    ) -> SpriteGroupId {
+        let view_sprite = create_array_view(tex);
         let sampler_sprite = gpu
             .device
             .create_sampler(&wgpu::SamplerDescriptor::default());
-        let tex_bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &self.texture_bind_group_layout,
-            entries: &[
-                // One for the texture, one for the sampler
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view_sprite),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler_sprite),
-                },
-            ],
-        });
-        let buffer_world = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: world_transforms.len() as u64 * std::mem::size_of::<Transform>() as u64,
-            usage: if USE_STORAGE {
-                wgpu::BufferUsages::STORAGE
-            } else {
-                wgpu::BufferUsages::VERTEX
-            } | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-        let buffer_sheet = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size: sheet_regions.len() as u64 * std::mem::size_of::<SheetRegion>() as u64,
-            usage: if USE_STORAGE {
-                wgpu::BufferUsages::STORAGE
-            } else {
-                wgpu::BufferUsages::VERTEX
-            } | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let tex_bind_group = create_texture_bind_group(
+            gpu,
+            &self.texture_bind_group_layout,
+            &view_sprite,
+            &sampler_sprite,
+        );
+        let colors = vec![Color::WHITE; world_transforms.len()];
+        let buffer_world = create_sprite_buffer(
+            gpu,
+            world_transforms.len() * std::mem::size_of::<Transform>(),
+        );
+        let buffer_sheet = create_sprite_buffer(
+            gpu,
+            sheet_regions.len() * std::mem::size_of::<SheetRegion>(),
+        );
+        let buffer_color = create_sprite_buffer(gpu, colors.len() * std::mem::size_of::<Color>());
         let camera_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
             label: None,
             size: std::mem::size_of::<GPUCamera>() as u64,
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
-        let sprite_bind_group = if USE_STORAGE {
-            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &self.sprite_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: camera_buffer.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: buffer_world.as_entire_binding(),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 2,
-                        resource: buffer_sheet.as_entire_binding(),
-                    },
-                ],
-            })
-        } else {
-            gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                label: None,
-                layout: &self.sprite_bind_group_layout,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding(),
-                }],
-            })
-        };
+        let sprite_bind_group = create_sprite_bind_group(
+            gpu,
+            &self.sprite_bind_group_layout,
+            &camera_buffer,
+            &buffer_world,
+            &buffer_sheet,
+            &buffer_color,
+        );
         gpu.queue
             .write_buffer(&buffer_world, 0, bytemuck::cast_slice(&world_transforms));
         gpu.queue
             .write_buffer(&buffer_sheet, 0, bytemuck::cast_slice(&sheet_regions));
+        gpu.queue
+            .write_buffer(&buffer_color, 0, bytemuck::cast_slice(&colors));
         gpu.queue
             .write_buffer(&camera_buffer, 0, bytemuck::bytes_of(&camera));
-        self.groups.push(SpriteGroup {
+        let group = SpriteGroup {
             world_buffer: buffer_world,
             sheet_buffer: buffer_sheet,
+            color_buffer: buffer_color,
             world_transforms,
             sheet_regions,
+            colors,
+            tex_view: view_sprite,
+            sampler: sampler_sprite,
             tex_bind_group,
             sprite_bind_group,
             camera,
             camera_buffer,
-        });
-        self.groups.len() - 1
+            blend: BlendMode::Cutout,
+            visible: true,
+            material: None,
+            material_params: None,
+            dirty: Cell::new(0..0),
+            created: self.groups_created,
+        };
+        self.groups_created += 1;
+        let index = match self.groups.iter().position(|slot| slot.group.is_none()) {
+            Some(index) => {
+                self.groups[index].group = Some(group);
+                index
+            }
+            None => {
+                self.groups.push(GroupSlot {
+                    generation: 0,
+                    group: Some(group),
+                });
+                self.groups.len() - 1
+            }
+        };
+        SpriteGroupId {
+            index: index as u32,
+            generation: self.groups[index].generation,
+        }
+    }
+    fn lookup(groups: &[GroupSlot], which: SpriteGroupId) -> Option<&SpriteGroup> {
+        groups
+            .get(which.index())
+            .filter(|slot| slot.generation == which.generation)
+            .and_then(|slot| slot.group.as_ref())
+    }
+    fn lookup_mut(groups: &mut [GroupSlot], which: SpriteGroupId) -> Option<&mut SpriteGroup> {
+        groups
+            .get_mut(which.index())
+            .filter(|slot| slot.generation == which.generation)
+            .and_then(|slot| slot.group.as_mut())
+    }
+    fn group(&self, which: SpriteGroupId) -> &SpriteGroup {
+        Self::lookup(&self.groups, which)
+            .unwrap_or_else(|| panic!("{which:?} was used after its sprite group was removed"))
+    }
+    fn group_mut(&mut self, which: SpriteGroupId) -> &mut SpriteGroup {
+        Self::lookup_mut(&mut self.groups, which)
+            .unwrap_or_else(|| panic!("{which:?} was used after its sprite group was removed"))
     }
     /// Returns the number of sprite groups
     pub fn sprite_group_count(&self) -> usize {
-        self.groups.len()
+        self.groups
+            .iter()
+            .filter(|slot| slot.group.is_some())
+            .count()
+    }
+    /// Returns handles to all the live sprite groups, in drawing order.
+    pub fn sprite_groups(&self) -> impl Iterator<Item = SpriteGroupId> + '_ {
+        self.groups
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.group.is_some())
+            .map(|(index, slot)| SpriteGroupId {
+                index: index as u32,
+                generation: slot.generation,
+            })
+    }
+    /// Whether the given handle still refers to a live sprite group.
+    pub fn contains_sprite_group(&self, which: SpriteGroupId) -> bool {
+        Self::lookup(&self.groups, which).is_some()
     }
-    /// Deletes a sprite group.  Note that this currently invalidates
-    /// all the old handles, which is not great.  Only use it on the
-    /// last sprite group if that matters to you.
-    pub fn remove_sprite_group(&mut self, which: usize) {
-        self.groups.remove(which);
+    /// Deletes a sprite group.  Handles to other groups remain valid;
+    /// `which` and any copies of it become stale.  Panics if the
+    /// group was already removed.
+    pub fn remove_sprite_group(&mut self, which: SpriteGroupId) {
+        self.group(which);
+        let slot = &mut self.groups[which.index()];
+        slot.group = None;
+        slot.generation += 1;
     }
     /// Reports the size of the given sprite group.
-    pub fn sprite_group_size(&self, which: usize) -> usize {
-        self.groups[which].world_transforms.len()
+    pub fn sprite_group_size(&self, which: SpriteGroupId) -> usize {
+        self.group(which).world_transforms.len()
     }
     /// Resizes a sprite group.  If the new size is smaller, this is
     /// very cheap; if it's larger than it's ever been before, it
     /// might involve reallocating the [`Vec<Transform>`],
     /// [`Vec<SheetRegion>`], or the GPU buffer used to draw sprites,
     /// so it could be expensive.
-    pub fn resize_sprite_group(&mut self, gpu: &WGPU, which: usize, len: usize) -> usize {
-        let group = &mut self.groups[which];
+    pub fn resize_sprite_group(&mut self, gpu: &WGPU, which: SpriteGroupId, len: usize) -> usize {
+        let group = Self::lookup_mut(&mut self.groups, which)
+            .unwrap_or_else(|| panic!("{which:?} was used after its sprite group was removed"));
         let old_len = group.world_transforms.len();
         if old_len == len {
             return old_len;
@@ -435,111 +868,287 @@ impl SpriteRenderer {
         // shrink or grow sprite vecs
         group.world_transforms.resize(len, Transform::zeroed());
         group.sheet_regions.resize(len, SheetRegion::zeroed());
+        group.colors.resize(len, Color::WHITE);
         // realloc buffer if needed, remake sprite_bind_group if using storage buffers
         let new_size = len * std::mem::size_of::<Transform>();
         if new_size > group.world_buffer.size() as usize {
-            group.world_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-                label: None,
-                size: new_size as u64,
-                usage: if USE_STORAGE {
-                    wgpu::BufferUsages::STORAGE
-                } else {
-                    wgpu::BufferUsages::VERTEX
-                } | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
-            group.sheet_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-                label: None,
-                size: new_size as u64,
-                usage: if USE_STORAGE {
-                    wgpu::BufferUsages::STORAGE
-                } else {
-                    wgpu::BufferUsages::VERTEX
-                } | wgpu::BufferUsages::COPY_DST,
-                mapped_at_creation: false,
-            });
-            if USE_STORAGE {
-                group.sprite_bind_group =
-                    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                        label: None,
-                        layout: &self.sprite_bind_group_layout,
-                        entries: &[
-                            wgpu::BindGroupEntry {
-                                binding: 0,
-                                resource: group.camera_buffer.as_entire_binding(),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 1,
-                                resource: group.world_buffer.as_entire_binding(),
-                            },
-                            wgpu::BindGroupEntry {
-                                binding: 2,
-                                resource: group.sheet_buffer.as_entire_binding(),
-                            },
-                        ],
-                    });
-            };
-            gpu.queue.write_buffer(
+            group.world_buffer = create_sprite_buffer(gpu, new_size);
+            group.sheet_buffer =
+                create_sprite_buffer(gpu, len * std::mem::size_of::<SheetRegion>());
+            group.color_buffer = create_sprite_buffer(gpu, len * std::mem::size_of::<Color>());
+            group.sprite_bind_group = create_sprite_bind_group(
+                gpu,
+                &self.sprite_bind_group_layout,
+                &group.camera_buffer,
                 &group.world_buffer,
-                0,
-                bytemuck::cast_slice(&group.world_transforms),
-            );
-            gpu.queue.write_buffer(
                 &group.sheet_buffer,
-                0,
-                bytemuck::cast_slice(&group.sheet_regions),
+                &group.color_buffer,
             );
+            group.upload_all(gpu);
+        } else if len > old_len {
+            group.mark_dirty(old_len..len);
         }
         old_len
     }
     /// Set the given camera transform on all sprite groups.  Uploads to the GPU.
     pub fn set_camera_all(&mut self, gpu: &WGPU, camera: GPUCamera) {
-        for sg_index in 0..self.groups.len() {
-            self.set_camera(gpu, sg_index, camera);
+        for sg in self
+            .groups
+            .iter_mut()
+            .filter_map(|slot| slot.group.as_mut())
+        {
+            sg.camera = camera;
+            gpu.queue
+                .write_buffer(&sg.camera_buffer, 0, bytemuck::bytes_of(&sg.camera));
         }
     }
     /// Set the given camera transform on a specific sprite group.  Uploads to the GPU.
-    pub fn set_camera(&mut self, gpu: &WGPU, which: usize, camera: GPUCamera) {
-        let sg = &mut self.groups[which];
+    pub fn set_camera(&mut self, gpu: &WGPU, which: SpriteGroupId, camera: GPUCamera) {
+        let sg = self.group_mut(which);
         sg.camera = camera;
         gpu.queue
             .write_buffer(&sg.camera_buffer, 0, bytemuck::bytes_of(&sg.camera));
     }
-    /// Send a range of stored sprite data for a particular group to the GPU.
-    /// You must call this yourself after modifying sprite data.
-    pub fn upload_sprites(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
+    /// Change how a sprite group is blended with what's behind it.
+    /// Uploads the group's sprites to the GPU.
+    pub fn set_blend_mode(&mut self, gpu: &WGPU, which: SpriteGroupId, blend: BlendMode) {
+        let group = self.group_mut(which);
+        group.blend = blend;
+        group.upload_all(gpu);
+    }
+    /// Reports how a sprite group is blended.
+    pub fn blend_mode(&self, which: SpriteGroupId) -> BlendMode {
+        self.group(which).blend
+    }
+    /// Draw a sprite group with a material from
+    /// [`SpriteRenderer::add_material`], or with the built-in shader
+    /// if `material` is `None`.  The group's material parameters are
+    /// kept if the new material's are the same size, and are
+    /// otherwise all zero until set with
+    /// [`SpriteRenderer::set_material_params`].
+    pub fn set_material(&mut self, gpu: &WGPU, which: SpriteGroupId, material: Option<MaterialId>) {
+        if let Some(id) = material {
+            assert!(
+                id.0 < self.materials.len(),
+                "{id:?} isn't a material of this renderer"
+            );
+        }
+        let group = Self::lookup_mut(&mut self.groups, which)
+            .unwrap_or_else(|| panic!("{which:?} was used after its sprite group was removed"));
+        group.material = material;
+        let Some(id) = material else {
+            return;
+        };
+        let size = self.materials[id.0].params_size;
+        if !matches!(&group.material_params, Some((buffer, _)) if buffer.size() == size as u64) {
+            group.material_params = Some(create_material_params(
+                gpu,
+                &self.material_bind_group_layout,
+                &vec![0; size],
+            ));
+        }
+    }
+    /// Reports which material a sprite group is drawn with, if any.
+    pub fn material(&self, which: SpriteGroupId) -> Option<MaterialId> {
+        self.group(which).material
+    }
+    /// Set the uniform a sprite group's material sees at
+    /// `@group(2) @binding(0)`.  `params` must have the same layout
+    /// as the uniform in the shader; it's padded with zeros to a
+    /// multiple of 16 bytes, so trailing padding can be left out.
+    /// Panics if the group has no material, or if `params` isn't the
+    /// size given to [`SpriteRenderer::add_material`] (after padding).
+    pub fn set_material_params<P: Pod>(&mut self, gpu: &WGPU, which: SpriteGroupId, params: &P) {
+        let mut bytes = bytemuck::bytes_of(params).to_vec();
+        bytes.resize(material_params_size(bytes.len()), 0);
+        let group = Self::lookup_mut(&mut self.groups, which)
+            .unwrap_or_else(|| panic!("{which:?} was used after its sprite group was removed"));
+        let (Some(_), Some((buffer, _))) = (group.material, &group.material_params) else {
+            panic!("{which:?} has no material to set the parameters of");
+        };
+        assert_eq!(
+            buffer.size(),
+            bytes.len() as u64,
+            "Material parameters for {which:?} are the wrong size"
+        );
+        gpu.queue.write_buffer(buffer, 0, &bytes);
+    }
+    /// Show or hide a sprite group.  Hidden groups keep their sprites
+    /// but aren't drawn.
+    pub fn set_visible(&mut self, which: SpriteGroupId, visible: bool) {
+        self.group_mut(which).visible = visible;
+    }
+    /// Reports whether a sprite group is drawn.
+    pub fn is_visible(&self, which: SpriteGroupId) -> bool {
+        self.group(which).visible
+    }
+    /// Change how a sprite group's texture is sampled, for example
+    /// to use linear filtering or repeating texture coordinates (see
+    /// [`sampler_descriptor`]).  New sprite groups use
+    /// [`wgpu::SamplerDescriptor::default()`], i.e. nearest-neighbor
+    /// filtering with coordinates clamped to the edge of the texture.
+    pub fn set_sampler(
+        &mut self,
+        gpu: &WGPU,
+        which: SpriteGroupId,
+        desc: &wgpu::SamplerDescriptor,
+    ) {
+        let group = Self::lookup_mut(&mut self.groups, which)
+            .unwrap_or_else(|| panic!("{which:?} was used after its sprite group was removed"));
+        group.sampler = gpu.device.create_sampler(desc);
+        group.tex_bind_group = create_texture_bind_group(
+            gpu,
+            &self.texture_bind_group_layout,
+            &group.tex_view,
+            &group.sampler,
+        );
+    }
+    /// Draw a sprite group from a different texture, keeping its
+    /// sprites, camera, sampler and blend mode.  Sheet regions are
+    /// in pixels, so the new texture should lay out its sprites the
+    /// same way as the old one (e.g. an alternate theme exported
+    /// from the same sheet).
+    pub fn set_texture(&mut self, gpu: &WGPU, which: SpriteGroupId, tex: &wgpu::Texture) {
+        let group = Self::lookup_mut(&mut self.groups, which)
+            .unwrap_or_else(|| panic!("{which:?} was used after its sprite group was removed"));
+        group.tex_view = create_array_view(tex);
+        group.tex_bind_group = create_texture_bind_group(
+            gpu,
+            &self.texture_bind_group_layout,
+            &group.tex_view,
+            &group.sampler,
+        );
+    }
+    /// Send a range of stored sprite data for a particular group to
+    /// the GPU right away.  Changes made through
+    /// [`SpriteRenderer::get_sprites_mut`] and friends are uploaded
+    /// automatically before rendering, so this is rarely needed.
+    /// Groups using [`BlendMode::Alpha`] always upload all their
+    /// sprites, since they need to be re-sorted.
+    pub fn upload_sprites(&mut self, gpu: &WGPU, which: SpriteGroupId, range: Range<usize>) {
         self.upload_world_transforms(gpu, which, range.clone());
-        self.upload_sheet_regions(gpu, which, range);
+        self.upload_sheet_regions(gpu, which, range.clone());
+        self.upload_colors(gpu, which, range);
     }
     /// Upload only position changes to the GPU
-    pub fn upload_world_transforms(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
-        gpu.queue.write_buffer(
-            &self.groups[which].world_buffer,
-            range.start as u64,
-            bytemuck::cast_slice(&self.groups[which].world_transforms[range]),
-        );
+    pub fn upload_world_transforms(
+        &mut self,
+        gpu: &WGPU,
+        which: SpriteGroupId,
+        range: Range<usize>,
+    ) {
+        let group = self.group(which);
+        if group.blend == BlendMode::Alpha {
+            group.upload_all(gpu);
+            return;
+        }
+        write_range(gpu, &group.world_buffer, &group.world_transforms, range);
     }
     /// Upload only visual changes to the GPU
-    pub fn upload_sheet_regions(&mut self, gpu: &WGPU, which: usize, range: Range<usize>) {
-        gpu.queue.write_buffer(
-            &self.groups[which].sheet_buffer,
-            range.start as u64,
-            bytemuck::cast_slice(&self.groups[which].sheet_regions[range]),
-        );
+    pub fn upload_sheet_regions(&mut self, gpu: &WGPU, which: SpriteGroupId, range: Range<usize>) {
+        let group = self.group(which);
+        if group.blend == BlendMode::Alpha {
+            group.upload_all(gpu);
+            return;
+        }
+        write_range(gpu, &group.sheet_buffer, &group.sheet_regions, range);
+    }
+    /// Upload only tint changes to the GPU
+    pub fn upload_colors(&mut self, gpu: &WGPU, which: SpriteGroupId, range: Range<usize>) {
+        let group = self.group(which);
+        if group.blend == BlendMode::Alpha {
+            group.upload_all(gpu);
+            return;
+        }
+        write_range(gpu, &group.color_buffer, &group.colors, range);
+    }
+    /// Get a read-only slice of a specified sprite group's tints.
+    pub fn get_colors(&self, which: SpriteGroupId) -> &[Color] {
+        &self.group(which).colors
+    }
+    /// Get a mutable slice of a specified sprite group's tints.  The
+    /// whole group is uploaded before it's next drawn.
+    pub fn get_colors_mut(&mut self, which: SpriteGroupId) -> &mut [Color] {
+        let group = self.group_mut(which);
+        group.mark_dirty(0..group.colors.len());
+        &mut group.colors
+    }
+    /// Like [`SpriteRenderer::get_colors_mut`], but only for the
+    /// tints in `range`, so only those are uploaded before the group
+    /// is next drawn.
+    pub fn get_color_range_mut(
+        &mut self,
+        which: SpriteGroupId,
+        range: Range<usize>,
+    ) -> &mut [Color] {
+        let group = self.group_mut(which);
+        group.mark_dirty(range.clone());
+        &mut group.colors[range]
     }
     /// Get a read-only slice of a specified sprite group's world transforms and texture regions.
-    pub fn get_sprites(&self, which: usize) -> (&[Transform], &[SheetRegion]) {
+    pub fn get_sprites(&self, which: SpriteGroupId) -> (&[Transform], &[SheetRegion]) {
+        let group = self.group(which);
+        (&group.world_transforms, &group.sheet_regions)
+    }
+    /// Get a mutable slice of a specified sprite group's world
+    /// transforms and texture regions.  The whole group is uploaded
+    /// before it's next drawn; use
+    /// [`SpriteRenderer::get_sprite_range_mut`] to upload less.
+    pub fn get_sprites_mut(
+        &mut self,
+        which: SpriteGroupId,
+    ) -> (&mut [Transform], &mut [SheetRegion]) {
+        let group = self.group_mut(which);
+        group.mark_dirty(0..group.world_transforms.len());
+        (&mut group.world_transforms, &mut group.sheet_regions)
+    }
+    /// Like [`SpriteRenderer::get_sprites_mut`], but only for the
+    /// sprites in `range`, so only those are uploaded before the
+    /// group is next drawn.
+    pub fn get_sprite_range_mut(
+        &mut self,
+        which: SpriteGroupId,
+        range: Range<usize>,
+    ) -> (&mut [Transform], &mut [SheetRegion]) {
+        let group = self.group_mut(which);
+        group.mark_dirty(range.clone());
         (
-            &self.groups[which].world_transforms,
-            &self.groups[which].sheet_regions,
+            &mut group.world_transforms[range.clone()],
+            &mut group.sheet_regions[range],
         )
     }
-    /// Get a mutable slice of a specified sprite group's world transforms and texture regions.
-    pub fn get_sprites_mut(&mut self, which: usize) -> (&mut [Transform], &mut [SheetRegion]) {
-        let group = &mut self.groups[which];
-        (&mut group.world_transforms, &mut group.sheet_regions)
+    /// Like [`SpriteRenderer::get_sprites`], but returns `None` if the group has been removed.
+    pub fn try_get_sprites(&self, which: SpriteGroupId) -> Option<(&[Transform], &[SheetRegion])> {
+        Self::lookup(&self.groups, which)
+            .map(|group| (&group.world_transforms[..], &group.sheet_regions[..]))
+    }
+    /// Like [`SpriteRenderer::get_sprites_mut`], but returns `None` if the group has been removed.
+    pub fn try_get_sprites_mut(
+        &mut self,
+        which: SpriteGroupId,
+    ) -> Option<(&mut [Transform], &mut [SheetRegion])> {
+        Self::lookup_mut(&mut self.groups, which).map(|group| {
+            group.mark_dirty(0..group.world_transforms.len());
+            (
+                &mut group.world_transforms[..],
+                &mut group.sheet_regions[..],
+            )
+        })
+    }
+    /// Upload every sprite changed since it was last uploaded.
+    /// [`crate::Renderer::render`] and
+    /// [`crate::Renderer::render_into`] do this for you; call it
+    /// yourself before [`SpriteRenderer::render`] if you use that
+    /// directly.
+    pub fn flush(&self, gpu: &WGPU) {
+        for group in self.groups.iter().filter_map(|slot| slot.group.as_ref()) {
+            group.flush(gpu);
+        }
     }
-    /// Render the given range of sprite groups into the given pass.
+    /// Render the given range of sprite group slots (see
+    /// [`SpriteGroupId::index`]) into the given pass.  Groups using
+    /// [`BlendMode::Cutout`] are drawn first, followed by alpha
+    /// blended groups.  Hidden groups are skipped.
     pub fn render<'s, 'pass>(
         &'s self,
         rpass: &mut wgpu::RenderPass<'pass>,
@@ -547,7 +1156,6 @@ impl SpriteRenderer {
     ) where
         's: 'pass,
     {
-        rpass.set_pipeline(&self.pipeline);
         let low = match which.start_bound() {
             std::ops::Bound::Included(&x) => x,
             std::ops::Bound::Excluded(&x) => x + 1,
@@ -558,19 +1166,389 @@ impl SpriteRenderer {
             std::ops::Bound::Excluded(&x) => x,
             std::ops::Bound::Unbounded => self.groups.len(),
         };
-        for group in self.groups[low..high].iter() {
-            if !USE_STORAGE {
-                rpass.set_vertex_buffer(0, group.world_buffer.slice(..));
-                rpass.set_vertex_buffer(0, group.sheet_buffer.slice(..));
+        let high = high.min(self.groups.len());
+        self.render_groups(rpass, low.min(high)..high, &[]);
+    }
+    /// Render every visible group except for those in `exclude`.
+    pub(crate) fn render_excluding<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        exclude: &[SpriteGroupId],
+    ) where
+        's: 'pass,
+    {
+        self.render_groups(rpass, 0..self.groups.len(), exclude);
+    }
+    /// Render the visible groups in the given range of slots, except
+    /// for those in `exclude`.
+    fn render_groups<'s, 'pass>(
+        &'s self,
+        rpass: &mut wgpu::RenderPass<'pass>,
+        slots: Range<usize>,
+        exclude: &[SpriteGroupId],
+    ) where
+        's: 'pass,
+    {
+        let start = slots.start;
+        let groups = || {
+            self.groups[slots.clone()]
+                .iter()
+                .enumerate()
+                .filter(|(i, slot)| {
+                    !exclude.contains(&SpriteGroupId {
+                        index: (start + i) as u32,
+                        generation: slot.generation,
+                    })
+                })
+                .filter_map(|(_, slot)| slot.group.as_ref())
+                .filter(|group| group.visible)
+        };
+        for group in groups().filter(|g| g.blend == BlendMode::Cutout) {
+            self.draw_group(rpass, group);
+        }
+        // Alpha groups don't write depth, so they're drawn in a fixed
+        // order rather than whatever order their slots are in
+        let mut translucent: Vec<_> = groups().filter(|g| g.blend != BlendMode::Cutout).collect();
+        translucent.sort_by_key(|group| group.created);
+        for group in translucent {
+            self.draw_group(rpass, group);
+        }
+    }
+    /// Draw one group with its material, or the built-in shader.
+    fn draw_group<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>, group: &'s SpriteGroup)
+    where
+        's: 'pass,
+    {
+        match (group.material, &group.material_params) {
+            (Some(id), Some((_, params))) => {
+                let material = &self.materials[id.0];
+                rpass.set_pipeline(match group.blend {
+                    BlendMode::Cutout => &material.pipeline,
+                    BlendMode::Alpha | BlendMode::AlphaUnsorted => &material.blend_pipeline,
+                });
+                rpass.set_bind_group(2, params, &[]);
             }
-            rpass.set_bind_group(0, &group.sprite_bind_group, &[]);
-            rpass.set_bind_group(1, &group.tex_bind_group, &[]);
-            // draw two triangles per sprite, and sprites-many sprites.
-            // this uses instanced drawing, but it would also be okay
-            // to draw 6 * sprites.len() vertices and use modular arithmetic
-            // to figure out which sprite we're drawing.
-            assert_eq!(group.world_transforms.len(), group.sheet_regions.len());
-            rpass.draw(0..6, 0..group.world_transforms.len() as u32);
+            _ => rpass.set_pipeline(match group.blend {
+                BlendMode::Cutout => &self.pipeline,
+                BlendMode::Alpha | BlendMode::AlphaUnsorted => &self.blend_pipeline,
+            }),
+        }
+        group.draw(rpass);
+    }
+}
+
+/// Write the elements of `data` in `range` to the same elements of `buffer`.
+fn write_range<T: Pod>(gpu: &WGPU, buffer: &wgpu::Buffer, data: &[T], range: Range<usize>) {
+    gpu.queue.write_buffer(
+        buffer,
+        (range.start * std::mem::size_of::<T>()) as u64,
+        bytemuck::cast_slice(&data[range]),
+    );
+}
+
+/// A view of every layer of `tex` as a 2D array texture.
+pub(crate) fn create_array_view(tex: &wgpu::Texture) -> wgpu::TextureView {
+    tex.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2Array),
+        base_array_layer: 0,
+        array_layer_count: match tex.depth_or_array_layers() {
+            0 => Some(1),
+            layers => Some(layers),
+        },
+        ..Default::default()
+    })
+}
+
+/// A sampler description using the given filter for magnification,
+/// minification, and mipmaps, and the given address mode on both
+/// axes.  Use [`wgpu::FilterMode::Nearest`] for crisp pixel art and
+/// [`wgpu::FilterMode::Linear`] for smooth, photographic textures.
+pub fn sampler_descriptor(
+    filter: wgpu::FilterMode,
+    address: wgpu::AddressMode,
+) -> wgpu::SamplerDescriptor<'static> {
+    wgpu::SamplerDescriptor {
+        address_mode_u: address,
+        address_mode_v: address,
+        address_mode_w: address,
+        mag_filter: filter,
+        min_filter: filter,
+        mipmap_filter: filter,
+        ..Default::default()
+    }
+}
+
+fn create_texture_bind_group(
+    gpu: &WGPU,
+    layout: &wgpu::BindGroupLayout,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            // One for the texture, one for the sampler
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+/// Uniform buffers are bound in multiples of 16 bytes, and bind
+/// groups can't be empty, so materials without parameters get 16
+/// bytes anyway.
+fn material_params_size(size: usize) -> usize {
+    wgpu::util::align_to(size.max(1), 16)
+}
+
+/// Create a uniform buffer holding `bytes` for a material, and a bind
+/// group for it.
+fn create_material_params(
+    gpu: &WGPU,
+    layout: &wgpu::BindGroupLayout,
+    bytes: &[u8],
+) -> (wgpu::Buffer, wgpu::BindGroup) {
+    let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("material params"),
+        size: bytes.len() as u64,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    gpu.queue.write_buffer(&buffer, 0, bytes);
+    let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("material params"),
+        layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding(),
+        }],
+    });
+    (buffer, bind_group)
+}
+
+/// Create a storage or vertex buffer (depending on [`USE_STORAGE`])
+/// holding `size` bytes of per-sprite data.
+fn create_sprite_buffer(gpu: &WGPU, size: usize) -> wgpu::Buffer {
+    gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: size as u64,
+        usage: if USE_STORAGE {
+            wgpu::BufferUsages::STORAGE
+        } else {
+            wgpu::BufferUsages::VERTEX
+        } | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Bind a sprite group's camera and, when [`USE_STORAGE`] is set,
+/// its per-sprite buffers.
+fn create_sprite_bind_group(
+    gpu: &WGPU,
+    layout: &wgpu::BindGroupLayout,
+    camera_buffer: &wgpu::Buffer,
+    world_buffer: &wgpu::Buffer,
+    sheet_buffer: &wgpu::Buffer,
+    color_buffer: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    if USE_STORAGE {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: world_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: sheet_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: color_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    } else {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        })
+    }
+}
+
+/// Build a sprite pipeline using the vertex stage of `shader`, the
+/// given fragment entry point, and the blending behavior of `blend`.
+fn create_pipeline(
+    gpu: &WGPU,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    fs_entry: &str,
+    blend: BlendMode,
+) -> wgpu::RenderPipeline {
+    gpu.device
+        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: if USE_STORAGE {
+                    "vs_storage_main"
+                } else {
+                    "vs_vbuf_main"
+                },
+                buffers: if USE_STORAGE {
+                    &[]
+                } else {
+                    &[
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<Transform>() as u64,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &[
+                                // The packed half-float width and
+                                // height are unpacked in the shader.
+                                wgpu::VertexAttribute {
+                                    format: wgpu::VertexFormat::Uint32,
+                                    offset: 0,
+                                    shader_location: 0,
+                                },
+                                wgpu::VertexAttribute {
+                                    format: wgpu::VertexFormat::Float32x3,
+                                    offset: 4,
+                                    shader_location: 3,
+                                },
+                            ],
+                        },
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<SheetRegion>() as u64,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &[wgpu::VertexAttribute {
+                                format: wgpu::VertexFormat::Uint32x4,
+                                offset: 0,
+                                shader_location: 1,
+                            }],
+                        },
+                        wgpu::VertexBufferLayout {
+                            array_stride: std::mem::size_of::<Color>() as u64,
+                            step_mode: wgpu::VertexStepMode::Instance,
+                            attributes: &[wgpu::VertexAttribute {
+                                format: wgpu::VertexFormat::Unorm8x4,
+                                offset: 0,
+                                shader_location: 2,
+                            }],
+                        },
+                    ]
+                },
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: fs_entry,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: gpu.config.format,
+                    blend: match blend {
+                        BlendMode::Cutout => None,
+                        BlendMode::Alpha | BlendMode::AlphaUnsorted => {
+                            Some(wgpu::BlendState::ALPHA_BLENDING)
+                        }
+                    },
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: wgpu::TextureFormat::Depth32Float,
+                // Translucent sprites are depth tested against
+                // what's been drawn already but don't occlude
+                // each other; they're drawn back to front instead.
+                depth_write_enabled: blend == BlendMode::Cutout,
+                depth_compare: match blend {
+                    BlendMode::Cutout => wgpu::CompareFunction::Less,
+                    BlendMode::Alpha | BlendMode::AlphaUnsorted => wgpu::CompareFunction::LessEqual,
+                },
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GPUCamera;
+
+    fn assert_near([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) {
+        assert!(
+            (ax - bx).abs() < 1e-3 && (ay - by).abs() < 1e-3,
+            "{:?} != {:?}",
+            [ax, ay],
+            [bx, by]
+        );
+    }
+
+    #[test]
+    fn plain_camera_maps_window_corners_to_its_view() {
+        let camera = GPUCamera::new([10.0, 20.0], [320.0, 240.0]);
+        let window = [640.0, 480.0];
+        // Window y counts down from the top; world y counts up
+        assert_near(camera.screen_to_world([0.0, 0.0], window), [10.0, 260.0]);
+        assert_near(
+            camera.screen_to_world([640.0, 480.0], window),
+            [330.0, 20.0],
+        );
+        assert_near(
+            camera.world_to_screen([170.0, 140.0], window),
+            [320.0, 240.0],
+        );
+    }
+
+    #[test]
+    fn rotated_zoomed_camera_round_trips() {
+        let camera = GPUCamera {
+            focus: [0.25, 0.75],
+            rotation: 0.7,
+            zoom: 2.5,
+            ..GPUCamera::new([-30.0, 12.0], [200.0, 150.0])
+        };
+        let window = [800.0, 600.0];
+        for point in [[0.0, 0.0], [17.5, -42.0], [123.0, 456.0]] {
+            let screen = camera.world_to_screen(point, window);
+            assert_near(camera.screen_to_world(screen, window), point);
         }
+        for screen in [[0.0, 0.0], [400.0, 300.0], [799.0, 12.0]] {
+            let world = camera.screen_to_world(screen, window);
+            assert_near(camera.world_to_screen(world, window), screen);
+        }
+    }
+
+    #[test]
+    fn focus_stays_put_under_zoom_and_rotation() {
+        let camera = GPUCamera {
+            focus: [0.25, 0.75],
+            rotation: 1.0,
+            zoom: 3.0,
+            ..GPUCamera::new([0.0, 0.0], [100.0, 100.0])
+        };
+        // The focus is a quarter of the way across and a quarter of the way down
+        assert_near(
+            camera.world_to_screen([25.0, 75.0], [400.0, 400.0]),
+            [100.0, 100.0],
+        );
     }
 }
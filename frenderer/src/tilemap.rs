@@ -0,0 +1,669 @@
+//! A renderer for tilemap layers: grids of tiles drawn from a tileset
+//! in a spritesheet.  Each layer keeps its tile indices in a single
+//! GPU buffer, so large maps cost far less to store and update than
+//! one sprite per tile.  Tilemaps are drawn before sprite groups, into
+//! the same depth buffer.
+
+use std::{borrow::Cow, cell::Cell, ops::Range};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{sprites::create_array_view, GPUCamera, SheetRegion, WGPU};
+
+/// A tile index that draws nothing, leaving a hole in the map.
+pub const EMPTY_TILE: u16 = u16::MAX;
+
+/// A set of equally sized tiles laid out in rows within a
+/// [`SheetRegion`].  Tile 0 is at the region's top left corner, and
+/// indices count left to right, then top to bottom.  The region's
+/// sheet and depth are used for the whole tilemap; its flip and
+/// rotation flags are ignored.
+#[derive(Clone, Copy, Debug)]
+pub struct Tileset {
+    region: SheetRegion,
+    tile_w: u16,
+    tile_h: u16,
+}
+
+impl Tileset {
+    /// Creates a tileset of tiles `tile_w` by `tile_h` sheet pixels
+    /// filling `region`.  Panics if a tile is bigger than the region
+    /// or empty.
+    pub fn with_sheet_region(region: SheetRegion, [tile_w, tile_h]: [u16; 2]) -> Self {
+        assert!(
+            tile_w > 0 && tile_h > 0 && tile_w <= region.w && tile_h <= region.h,
+            "Tiles must fit within the tileset's sheet region"
+        );
+        Self {
+            region,
+            tile_w,
+            tile_h,
+        }
+    }
+    /// The number of tiles in this tileset.
+    pub fn tile_count(&self) -> usize {
+        self.tiles_per_row() as usize * (self.region.h / self.tile_h) as usize
+    }
+    /// The sheet region of the given tile, e.g. to draw it as a sprite.
+    pub fn tile_region(&self, tile: u16) -> SheetRegion {
+        let (col, row) = (tile % self.tiles_per_row(), tile / self.tiles_per_row());
+        SheetRegion::new(
+            self.region.sheet,
+            self.region.x + col * self.tile_w,
+            self.region.y + row * self.tile_h,
+            self.region.depth,
+            self.tile_w,
+            self.tile_h,
+        )
+    }
+    fn tiles_per_row(&self) -> u16 {
+        self.region.w / self.tile_w
+    }
+}
+
+/// A handle to a tilemap, returned by
+/// [`TilemapRenderer::add_tilemap`].  Like a
+/// [`crate::SpriteGroupId`], it stays valid when other tilemaps are
+/// removed, and using it after its own tilemap is removed panics.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TilemapId {
+    index: u32,
+    generation: u32,
+}
+
+/// The uniform data for one tilemap; must match `Tilemap` in the shader.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct TilemapParams {
+    position: [f32; 2],
+    tile_size: [f32; 2],
+    sheet_xy: [u32; 2],
+    sheet_tile: [u32; 2],
+    columns: u32,
+    rows: u32,
+    tiles_per_row: u32,
+    sheet_depth: u32,
+}
+
+/// A tilemap's tile indices, row by row from the top, along with
+/// which of them still need uploading.
+struct TileGrid {
+    columns: u32,
+    tiles: Vec<u16>,
+    /// Tiles changed since they were last uploaded; empty if none.
+    dirty: Cell<Range<usize>>,
+}
+
+impl TileGrid {
+    /// A grid `columns` tiles wide, all of which need uploading.
+    /// Panics unless `tiles` is a whole number of rows.
+    fn new(columns: u32, tiles: Vec<u16>) -> Self {
+        let rows = if columns == 0 {
+            0
+        } else {
+            tiles.len() / columns as usize
+        };
+        assert!(
+            rows > 0 && rows * columns as usize == tiles.len(),
+            "A tilemap needs a whole number of rows of {columns} tiles, not {}",
+            tiles.len()
+        );
+        Self {
+            columns,
+            dirty: Cell::new(0..tiles.len()),
+            tiles,
+        }
+    }
+    fn rows(&self) -> u32 {
+        self.tiles.len() as u32 / self.columns
+    }
+    /// The index in `tiles` of the given column and row.
+    fn offset(&self, [col, row]: [u32; 2]) -> usize {
+        assert!(
+            col < self.columns && row < self.rows(),
+            "Tile ({col}, {row}) is outside a {}x{} tilemap",
+            self.columns,
+            self.rows()
+        );
+        (row * self.columns + col) as usize
+    }
+    fn set(&mut self, pos: [u32; 2], tile: u16) {
+        let offset = self.offset(pos);
+        self.tiles[offset] = tile;
+        self.mark_dirty(offset..offset + 1);
+    }
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        let dirty = self.dirty.take();
+        self.dirty.set(if dirty.is_empty() {
+            range
+        } else {
+            dirty.start.min(range.start)..dirty.end.max(range.end)
+        });
+    }
+    /// The tiles changed since this was last called, widened to the
+    /// `u32`s the shader reads, and the index of the first of them.
+    fn take_dirty(&self) -> Option<(usize, Vec<u32>)> {
+        let dirty = self.dirty.take();
+        if dirty.is_empty() {
+            return None;
+        }
+        let tiles = self.tiles[dirty.clone()]
+            .iter()
+            .map(|&tile| tile.into())
+            .collect();
+        Some((dirty.start, tiles))
+    }
+}
+
+struct Tilemap {
+    tileset: Tileset,
+    grid: TileGrid,
+    tile_buffer: wgpu::Buffer,
+    position: [f32; 2],
+    tile_size: [f32; 2],
+    params_buffer: wgpu::Buffer,
+    camera_buffer: wgpu::Buffer,
+    tex_view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+    visible: bool,
+}
+
+impl Tilemap {
+    fn write_params(&self, gpu: &WGPU) {
+        let region = self.tileset.region;
+        let params = TilemapParams {
+            position: self.position,
+            tile_size: self.tile_size,
+            sheet_xy: [region.x.into(), region.y.into()],
+            sheet_tile: [self.tileset.tile_w.into(), self.tileset.tile_h.into()],
+            columns: self.grid.columns,
+            rows: self.grid.rows(),
+            tiles_per_row: self.tileset.tiles_per_row().into(),
+            sheet_depth: u32::from(region.sheet) | (u32::from(region.depth) << 16),
+        };
+        gpu.queue
+            .write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+    /// Upload the tiles changed since the last upload, if any.
+    fn flush(&self, gpu: &WGPU) {
+        if let Some((start, tiles)) = self.grid.take_dirty() {
+            gpu.queue.write_buffer(
+                &self.tile_buffer,
+                (start * std::mem::size_of::<u32>()) as u64,
+                bytemuck::cast_slice(&tiles),
+            );
+        }
+    }
+}
+
+struct TilemapSlot {
+    generation: u32,
+    map: Option<Tilemap>,
+}
+
+/// TilemapRenderer hosts a number of tilemap layers, each a grid of
+/// tile indices into a [`Tileset`] with its own position, tile size
+/// and [`GPUCamera`].  Row 0 of a map is its top row.
+pub struct TilemapRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    maps: Vec<TilemapSlot>,
+}
+
+impl TilemapRenderer {
+    pub(crate) fn new(gpu: &WGPU) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("tilemap"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("tilemap.wgsl"))),
+            });
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("tilemap"),
+                    entries: &[
+                        uniform_entry(0),
+                        uniform_entry(1),
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            // The vertex stage needs the texture's size
+                            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Texture {
+                                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                                view_dimension: wgpu::TextureViewDimension::D2Array,
+                                multisampled: false,
+                            },
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                    ],
+                });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("tilemap"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("tilemap"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    // One tile index per instance; this works with or
+                    // without storage buffers.
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<u32>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &[wgpu::VertexAttribute {
+                            format: wgpu::VertexFormat::Uint32,
+                            offset: 0,
+                            shader_location: 0,
+                        }],
+                    }],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(gpu.config.format.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+        Self {
+            pipeline,
+            bind_group_layout,
+            maps: vec![],
+        }
+    }
+    /// Create a tilemap `columns` tiles wide drawing `tiles` (row by
+    /// row from the top, so its length must be a multiple of
+    /// `columns`) from `tileset` in `tex`.  The map's bottom left
+    /// corner starts at the world origin, and each tile is as big in
+    /// world space as it is in the sheet; see
+    /// [`TilemapRenderer::set_position`] and
+    /// [`TilemapRenderer::set_tile_size`].  Panics if there are no
+    /// tiles or a tile isn't in the tileset.
+    pub fn add_tilemap(
+        &mut self,
+        gpu: &WGPU,
+        tex: &wgpu::Texture,
+        tileset: Tileset,
+        columns: u32,
+        tiles: Vec<u16>,
+        camera: GPUCamera,
+    ) -> TilemapId {
+        let grid = TileGrid::new(columns, tiles);
+        for &tile in &grid.tiles {
+            check_tile(&tileset, tile);
+        }
+        let tile_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tilemap tiles"),
+            size: (grid.tiles.len() * std::mem::size_of::<u32>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tilemap params"),
+            size: std::mem::size_of::<TilemapParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tilemap camera"),
+            size: std::mem::size_of::<GPUCamera>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        gpu.queue
+            .write_buffer(&camera_buffer, 0, bytemuck::bytes_of(&camera));
+        let view = create_array_view(tex);
+        let sampler = gpu
+            .device
+            .create_sampler(&wgpu::SamplerDescriptor::default());
+        let bind_group = create_bind_group(
+            gpu,
+            &self.bind_group_layout,
+            &camera_buffer,
+            &params_buffer,
+            &view,
+            &sampler,
+        );
+        let map = Tilemap {
+            tileset,
+            grid,
+            tile_buffer,
+            position: [0.0, 0.0],
+            tile_size: [tileset.tile_w.into(), tileset.tile_h.into()],
+            params_buffer,
+            camera_buffer,
+            tex_view: view,
+            bind_group,
+            visible: true,
+        };
+        map.write_params(gpu);
+        map.flush(gpu);
+        let index = match self.maps.iter().position(|slot| slot.map.is_none()) {
+            Some(index) => {
+                self.maps[index].map = Some(map);
+                index
+            }
+            None => {
+                self.maps.push(TilemapSlot {
+                    generation: 0,
+                    map: Some(map),
+                });
+                self.maps.len() - 1
+            }
+        };
+        TilemapId {
+            index: index as u32,
+            generation: self.maps[index].generation,
+        }
+    }
+    fn lookup(&self, which: TilemapId) -> Option<&Tilemap> {
+        self.maps
+            .get(which.index as usize)
+            .filter(|slot| slot.generation == which.generation)
+            .and_then(|slot| slot.map.as_ref())
+    }
+    fn map(&self, which: TilemapId) -> &Tilemap {
+        self.lookup(which)
+            .unwrap_or_else(|| panic!("{which:?} was used after its tilemap was removed"))
+    }
+    fn lookup_mut(maps: &mut [TilemapSlot], which: TilemapId) -> &mut Tilemap {
+        maps.get_mut(which.index as usize)
+            .filter(|slot| slot.generation == which.generation)
+            .and_then(|slot| slot.map.as_mut())
+            .unwrap_or_else(|| panic!("{which:?} was used after its tilemap was removed"))
+    }
+    fn map_mut(&mut self, which: TilemapId) -> &mut Tilemap {
+        Self::lookup_mut(&mut self.maps, which)
+    }
+    /// Returns whether `which` refers to a tilemap that hasn't been removed.
+    pub fn contains_tilemap(&self, which: TilemapId) -> bool {
+        self.lookup(which).is_some()
+    }
+    /// Deletes a tilemap.  Panics if it was already removed.
+    pub fn remove_tilemap(&mut self, which: TilemapId) {
+        self.map(which);
+        let slot = &mut self.maps[which.index as usize];
+        slot.map = None;
+        slot.generation += 1;
+    }
+    /// The number of columns and rows in a tilemap.
+    pub fn tilemap_size(&self, which: TilemapId) -> [u32; 2] {
+        let map = self.map(which);
+        [map.grid.columns, map.grid.rows()]
+    }
+    /// The tile at the given column and row (counting rows from the
+    /// top).  Panics if that's outside the map.
+    pub fn tile(&self, which: TilemapId, [col, row]: [u32; 2]) -> u16 {
+        let map = self.map(which);
+        map.grid.tiles[map.grid.offset([col, row])]
+    }
+    /// Change the tile at the given column and row (counting rows from
+    /// the top); only that tile is uploaded before the map is next
+    /// drawn.  Panics if that's outside the map or the tile isn't in
+    /// the tileset.
+    pub fn set_tile(&mut self, which: TilemapId, [col, row]: [u32; 2], tile: u16) {
+        let map = self.map_mut(which);
+        check_tile(&map.tileset, tile);
+        map.grid.set([col, row], tile);
+    }
+    /// All of a tilemap's tiles, row by row from the top.
+    pub fn tiles(&self, which: TilemapId) -> &[u16] {
+        &self.map(which).grid.tiles
+    }
+    /// All of a tilemap's tiles, row by row from the top, for
+    /// changing many at once.  The whole map is uploaded before it's
+    /// next drawn.
+    pub fn tiles_mut(&mut self, which: TilemapId) -> &mut [u16] {
+        let map = self.map_mut(which);
+        map.grid.mark_dirty(0..map.grid.tiles.len());
+        &mut map.grid.tiles
+    }
+    /// Move a tilemap so its bottom left corner is at `position` in
+    /// world space.
+    pub fn set_position(&mut self, gpu: &WGPU, which: TilemapId, position: [f32; 2]) {
+        let map = self.map_mut(which);
+        map.position = position;
+        map.write_params(gpu);
+    }
+    /// Change how big each of a tilemap's tiles is in world space.
+    pub fn set_tile_size(&mut self, gpu: &WGPU, which: TilemapId, tile_size: [f32; 2]) {
+        let map = self.map_mut(which);
+        map.tile_size = tile_size;
+        map.write_params(gpu);
+    }
+    /// Set the camera for a tilemap, e.g. every frame to scroll it
+    /// along with the sprite groups.
+    pub fn set_camera(&mut self, gpu: &WGPU, which: TilemapId, camera: GPUCamera) {
+        let map = self.map(which);
+        gpu.queue
+            .write_buffer(&map.camera_buffer, 0, bytemuck::bytes_of(&camera));
+    }
+    /// Change how a tilemap's tiles are sampled, as with
+    /// [`crate::SpriteRenderer::set_sampler`].  Linear filtering can
+    /// pick up texels from neighboring tiles in the tileset, so leave
+    /// some space between them if using it.
+    pub fn set_sampler(&mut self, gpu: &WGPU, which: TilemapId, desc: &wgpu::SamplerDescriptor) {
+        let map = Self::lookup_mut(&mut self.maps, which);
+        map.bind_group = create_bind_group(
+            gpu,
+            &self.bind_group_layout,
+            &map.camera_buffer,
+            &map.params_buffer,
+            &map.tex_view,
+            &gpu.device.create_sampler(desc),
+        );
+    }
+    /// Show or hide a tilemap.
+    pub fn set_visible(&mut self, which: TilemapId, visible: bool) {
+        self.map_mut(which).visible = visible;
+    }
+    /// Reports whether a tilemap is drawn.
+    pub fn is_visible(&self, which: TilemapId) -> bool {
+        self.map(which).visible
+    }
+    /// Upload every tile changed since it was last uploaded.
+    /// [`crate::Renderer::render`] and
+    /// [`crate::Renderer::render_into`] do this for you.
+    pub fn flush(&self, gpu: &WGPU) {
+        for map in self.maps.iter().filter_map(|slot| slot.map.as_ref()) {
+            map.flush(gpu);
+        }
+    }
+    /// Draw every visible tilemap into the given pass.
+    pub fn render<'s, 'pass>(&'s self, rpass: &mut wgpu::RenderPass<'pass>)
+    where
+        's: 'pass,
+    {
+        rpass.set_pipeline(&self.pipeline);
+        for map in self
+            .maps
+            .iter()
+            .filter_map(|slot| slot.map.as_ref())
+            .filter(|map| map.visible)
+        {
+            rpass.set_bind_group(0, &map.bind_group, &[]);
+            rpass.set_vertex_buffer(0, map.tile_buffer.slice(..));
+            rpass.draw(0..6, 0..map.grid.tiles.len() as u32);
+        }
+    }
+}
+
+fn create_bind_group(
+    gpu: &WGPU,
+    layout: &wgpu::BindGroupLayout,
+    camera_buffer: &wgpu::Buffer,
+    params_buffer: &wgpu::Buffer,
+    view: &wgpu::TextureView,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("tilemap"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: params_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+fn check_tile(tileset: &Tileset, tile: u16) {
+    assert!(
+        tile == EMPTY_TILE || (tile as usize) < tileset.tile_count(),
+        "Tile {tile} isn't in a tileset of {} tiles",
+        tileset.tile_count()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_tile, TileGrid, Tileset, EMPTY_TILE};
+    use crate::SheetRegion;
+
+    #[test]
+    fn partial_tiles_are_left_out_of_the_count() {
+        // 5 columns and 3 rows of 20x16 tiles, with some pixels left over
+        let tiles = Tileset::with_sheet_region(SheetRegion::new(2, 16, 8, 7, 100, 50), [20, 16]);
+        assert_eq!(tiles.tile_count(), 15);
+    }
+
+    #[test]
+    fn tiles_count_across_then_down_from_the_region_corner() {
+        let tiles = Tileset::with_sheet_region(SheetRegion::new(2, 16, 8, 7, 100, 50), [20, 16]);
+        let first = tiles.tile_region(0);
+        assert_eq!((first.sheet, first.depth), (2, 7));
+        assert_eq!((first.x, first.y, first.w, first.h), (16, 8, 20, 16));
+        let end_of_row = tiles.tile_region(4);
+        assert_eq!((end_of_row.x, end_of_row.y), (96, 8));
+        let second_row = tiles.tile_region(7);
+        assert_eq!((second_row.x, second_row.y), (56, 24));
+        let last = tiles.tile_region(14);
+        assert_eq!((last.x, last.y, last.w, last.h), (96, 40, 20, 16));
+    }
+
+    #[test]
+    #[should_panic(expected = "Tiles must fit")]
+    fn tiles_bigger_than_the_region_are_rejected() {
+        Tileset::with_sheet_region(SheetRegion::new(0, 0, 0, 0, 16, 16), [32, 8]);
+    }
+
+    #[test]
+    fn tiles_in_the_tileset_or_empty_are_accepted() {
+        let tiles = Tileset::with_sheet_region(SheetRegion::new(0, 0, 0, 0, 32, 16), [16, 16]);
+        check_tile(&tiles, 0);
+        check_tile(&tiles, 1);
+        check_tile(&tiles, EMPTY_TILE);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tile 2 isn't in a tileset of 2 tiles")]
+    fn tiles_outside_the_tileset_are_rejected() {
+        let tiles = Tileset::with_sheet_region(SheetRegion::new(0, 0, 0, 0, 32, 16), [16, 16]);
+        check_tile(&tiles, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "whole number of rows")]
+    fn grids_need_whole_rows() {
+        TileGrid::new(3, vec![0; 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "whole number of rows")]
+    fn grids_need_columns() {
+        TileGrid::new(0, vec![0; 4]);
+    }
+
+    #[test]
+    fn tiles_are_stored_row_by_row() {
+        let grid = TileGrid::new(3, vec![0; 6]);
+        assert_eq!(grid.rows(), 2);
+        assert_eq!(grid.offset([0, 0]), 0);
+        assert_eq!(grid.offset([2, 0]), 2);
+        assert_eq!(grid.offset([0, 1]), 3);
+        assert_eq!(grid.offset([2, 1]), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tile (3, 0) is outside a 3x2 tilemap")]
+    fn columns_past_the_edge_are_rejected() {
+        TileGrid::new(3, vec![0; 6]).set([3, 0], 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tile (0, 2) is outside a 3x2 tilemap")]
+    fn rows_past_the_bottom_are_rejected() {
+        TileGrid::new(3, vec![0; 6]).set([0, 2], 1);
+    }
+
+    #[test]
+    fn tiles_are_uploaded_as_u32s() {
+        let grid = TileGrid::new(2, vec![0, 7, 65534, EMPTY_TILE]);
+        // A new grid uploads everything, once
+        assert_eq!(grid.take_dirty(), Some((0, vec![0, 7, 65534, 65535])));
+        assert_eq!(grid.take_dirty(), None);
+    }
+
+    #[test]
+    fn only_changed_tiles_are_uploaded() {
+        let mut grid = TileGrid::new(3, vec![0; 9]);
+        grid.take_dirty();
+        grid.set([1, 1], 4);
+        assert_eq!(grid.take_dirty(), Some((4, vec![4])));
+        // Changes far apart upload everything between them
+        grid.set([2, 2], 8);
+        grid.set([1, 0], 1);
+        assert_eq!(grid.take_dirty(), Some((1, vec![1, 0, 0, 4, 0, 0, 0, 8])));
+        // Empty ranges don't count as changes
+        grid.mark_dirty(5..5);
+        assert_eq!(grid.take_dirty(), None);
+    }
+}
@@ -0,0 +1,93 @@
+// Draws a grid of tiles, one instance per tile, looking up each
+// tile's picture in a tileset by its index.
+
+var<private> VERTICES:array<vec2<f32>,6> = array<vec2<f32>,6>(
+    // Bottom left, bottom right, top left; then top left, bottom right, top right.
+    vec2<f32>(-0.5, -0.5),
+    vec2<f32>(0.5, -0.5),
+    vec2<f32>(-0.5, 0.5),
+    vec2<f32>(-0.5, 0.5),
+    vec2<f32>(0.5, -0.5),
+    vec2<f32>(0.5, 0.5)
+);
+
+// This must match Camera in shader.wgsl.
+struct Camera {
+    screen_pos: vec2<f32>,
+    screen_size: vec2<f32>,
+    focus: vec2<f32>,
+    rotation: f32,
+    zoom: f32,
+}
+
+struct Tilemap {
+    // the bottom left corner of the map in world space
+    position: vec2<f32>,
+    // the size of each tile in world space
+    tile_size: vec2<f32>,
+    // the top left corner of the tileset in the sheet, in pixels
+    sheet_xy: vec2<u32>,
+    // the size of each tile in the sheet, in pixels
+    sheet_tile: vec2<u32>,
+    columns: u32,
+    rows: u32,
+    tiles_per_row: u32,
+    // sheet layer in the low 16 bits, depth in the high 16 bits
+    sheet_depth: u32,
+}
+
+// This must match EMPTY_TILE.
+const EMPTY_TILE: u32 = 0xFFFFu;
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(0) @binding(1)
+var<uniform> map: Tilemap;
+@group(0) @binding(2)
+var t_tiles: texture_2d_array<f32>;
+@group(0) @binding(3)
+var s_tiles: sampler;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32, @builtin(instance_index) tile_index: u32, @location(0) tile: u32) -> VertexOutput {
+    if tile == EMPTY_TILE {
+        // Every vertex in the same place draws nothing
+        return VertexOutput(vec4(0.0, 0.0, 0.0, 1.0), vec2(0.0));
+    }
+    let norm_vert = VERTICES[in_vertex_index];
+    // Row 0 is the top of the map
+    let col = tile_index % map.columns;
+    let row = map.rows - 1u - tile_index / map.columns;
+    let center = map.position + (vec2(f32(col), f32(row)) + 0.5) * map.tile_size;
+    let world_pos = center + norm_vert * map.tile_size;
+    // zoom and rotate (the opposite way to the camera) around the focus,
+    // as in shader.wgsl
+    let focus = camera.focus * camera.screen_size;
+    let view_pos = world_pos - camera.screen_pos - focus;
+    let camsin = sin(camera.rotation);
+    let camcos = cos(camera.rotation);
+    let camera_pos = vec2(
+                          view_pos.x*camcos+view_pos.y*camsin,
+                          view_pos.y*camcos-view_pos.x*camsin
+                          ) * camera.zoom + focus;
+    let box_pos = camera_pos / (camera.screen_size*0.5);
+    let depth = f32(map.sheet_depth >> 16u) / 65535.0;
+    let ndc_pos = vec4(box_pos - 1.0, depth, 1.0);
+    let tex_size = vec2<f32>(textureDimensions(t_tiles));
+    let sheet_cell = vec2(tile % map.tiles_per_row, tile / map.tiles_per_row);
+    let tex_corner = vec2<f32>(map.sheet_xy + sheet_cell * map.sheet_tile);
+    let norm_uv = vec2(norm_vert.x + 0.5, 0.5 - norm_vert.y);
+    return VertexOutput(ndc_pos, (tex_corner + norm_uv * vec2<f32>(map.sheet_tile)) / tex_size);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_tiles, s_tiles, in.tex_coords, map.sheet_depth & 0xFFFFu);
+    if color.w < 0.2 { discard; }
+    return color;
+}
@@ -0,0 +1,303 @@
+//! Animated transitions between two sets of sprite groups, e.g. from
+//! a title screen to gameplay.  Start one with
+//! [`crate::Renderer::start_transition`] and advance it with
+//! [`crate::Renderer::update_transition`].  While it runs, the frame
+//! is drawn twice, once without the groups being transitioned to and
+//! once without the groups being transitioned from, and the two
+//! pictures are blended together.
+
+use std::borrow::Cow;
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{SpriteGroupId, WGPU};
+
+/// How one screen turns into another.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Transition {
+    /// Fade out to a solid color (linear RGB), then fade in from it.
+    Fade { color: [f32; 3] },
+    /// Fade smoothly from one screen to the other.
+    Crossfade,
+    /// Uncover the new screen behind an edge moving across the window.
+    Wipe { direction: WipeDirection },
+}
+
+/// Which way the edge of a [`Transition::Wipe`] moves.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WipeDirection {
+    /// Uncover the new screen from the right.
+    Left,
+    /// Uncover the new screen from the left.
+    Right,
+    /// Uncover the new screen from the bottom.
+    Up,
+    /// Uncover the new screen from the top.
+    Down,
+}
+
+/// The uniform data for the blend; must match `Params` in the shader.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct TransitionParams {
+    color: [f32; 4],
+    direction: [f32; 2],
+    progress: f32,
+    mode: u32,
+}
+
+struct ActiveTransition {
+    transition: Transition,
+    from: Vec<SpriteGroupId>,
+    to: Vec<SpriteGroupId>,
+    duration: f32,
+    elapsed: f32,
+    from_view: wgpu::TextureView,
+    to_view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+pub(crate) struct Transitioner {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params_buffer: wgpu::Buffer,
+    active: Option<ActiveTransition>,
+}
+
+impl Transitioner {
+    pub(crate) fn new(gpu: &WGPU) -> Self {
+        let shader = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("transition"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("transition.wgsl"))),
+            });
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None,
+        };
+        let bind_group_layout =
+            gpu.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some("transition"),
+                    entries: &[
+                        texture_entry(0),
+                        texture_entry(1),
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                            count: None,
+                        },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 3,
+                            visibility: wgpu::ShaderStages::FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
+                    ],
+                });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("transition"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("transition"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(gpu.config.format.into())],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+        let sampler = gpu.device.create_sampler(&crate::sampler_descriptor(
+            wgpu::FilterMode::Nearest,
+            wgpu::AddressMode::ClampToEdge,
+        ));
+        let params_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("transition params"),
+            size: std::mem::size_of::<TransitionParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params_buffer,
+            active: None,
+        }
+    }
+    pub(crate) fn start(
+        &mut self,
+        gpu: &WGPU,
+        transition: Transition,
+        from: &[SpriteGroupId],
+        to: &[SpriteGroupId],
+        duration: f32,
+    ) {
+        let from_view = gpu.create_frame_target("transition from");
+        let to_view = gpu.create_frame_target("transition to");
+        let bind_group = self.create_bind_group(gpu, &from_view, &to_view);
+        self.active = Some(ActiveTransition {
+            transition,
+            from: from.to_vec(),
+            to: to.to_vec(),
+            duration,
+            elapsed: 0.0,
+            from_view,
+            to_view,
+            bind_group,
+        });
+        self.write_params(gpu);
+    }
+    /// Advance the running transition by `dt` seconds.  Returns the
+    /// groups it was transitioning from and to if it just finished.
+    pub(crate) fn update(
+        &mut self,
+        gpu: &WGPU,
+        dt: f32,
+    ) -> Option<(Vec<SpriteGroupId>, Vec<SpriteGroupId>)> {
+        let active = self.active.as_mut()?;
+        active.elapsed += dt;
+        if active.elapsed >= active.duration {
+            return self.finish();
+        }
+        self.write_params(gpu);
+        None
+    }
+    /// Stop the running transition, returning the groups it was
+    /// transitioning from and to.
+    pub(crate) fn finish(&mut self) -> Option<(Vec<SpriteGroupId>, Vec<SpriteGroupId>)> {
+        self.active.take().map(|active| (active.from, active.to))
+    }
+    pub(crate) fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+    /// Recreate the frame targets to match the window size.
+    pub(crate) fn resize(&mut self, gpu: &WGPU) {
+        if let Some(mut active) = self.active.take() {
+            active.from_view = gpu.create_frame_target("transition from");
+            active.to_view = gpu.create_frame_target("transition to");
+            active.bind_group = self.create_bind_group(gpu, &active.from_view, &active.to_view);
+            self.active = Some(active);
+        }
+    }
+    /// If a transition is running, the views to draw the frames
+    /// before and after into, each with the groups to leave out of it.
+    pub(crate) fn frames(&self) -> Option<[(&wgpu::TextureView, &[SpriteGroupId]); 2]> {
+        self.active.as_ref().map(|active| {
+            [
+                (&active.from_view, &active.to[..]),
+                (&active.to_view, &active.from[..]),
+            ]
+        })
+    }
+    /// Blend the two frames into `view`.
+    pub(crate) fn composite(&self, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let Some(active) = &self.active else {
+            return;
+        };
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("transition"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &active.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+    fn write_params(&self, gpu: &WGPU) {
+        let Some(active) = &self.active else {
+            return;
+        };
+        let progress = if active.duration > 0.0 {
+            (active.elapsed / active.duration).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        let (color, direction, mode) = match active.transition {
+            Transition::Fade { color: [r, g, b] } => ([r, g, b, 1.0], [0.0, 0.0], 0),
+            Transition::Crossfade => ([0.0; 4], [0.0, 0.0], 1),
+            Transition::Wipe { direction } => {
+                // In texture space, y points down the window
+                let direction = match direction {
+                    WipeDirection::Left => [-1.0, 0.0],
+                    WipeDirection::Right => [1.0, 0.0],
+                    WipeDirection::Up => [0.0, -1.0],
+                    WipeDirection::Down => [0.0, 1.0],
+                };
+                ([0.0; 4], direction, 2)
+            }
+        };
+        let params = TransitionParams {
+            color,
+            direction,
+            progress,
+            mode,
+        };
+        gpu.queue
+            .write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+    fn create_bind_group(
+        &self,
+        gpu: &WGPU,
+        from_view: &wgpu::TextureView,
+        to_view: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("transition"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(from_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(to_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.params_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+}
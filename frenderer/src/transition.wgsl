@@ -0,0 +1,61 @@
+// Blends the frame drawn before a transition with the frame drawn
+// after it.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+struct Params {
+    color: vec4<f32>,
+    // for wipes, the direction the edge moves in texture space
+    direction: vec2<f32>,
+    // 0.0 at the start of the transition and 1.0 at the end
+    progress: f32,
+    // 0: fade through color, 1: crossfade, 2: wipe
+    mode: u32,
+}
+
+@group(0) @binding(0)
+var t_from: texture_2d<f32>;
+@group(0) @binding(1)
+var t_to: texture_2d<f32>;
+@group(0) @binding(2)
+var s_frame: sampler;
+@group(0) @binding(3)
+var<uniform> params: Params;
+
+// How soft the edge of a wipe is, in texture space
+const WIPE_EDGE: f32 = 0.02;
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    // (0,0), (2,0), (0,2) in texture space
+    let uv = vec2(f32((in_vertex_index << 1u) & 2u), f32(in_vertex_index & 2u));
+    let pos = vec4(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return VertexOutput(pos, uv);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let before = textureSample(t_from, s_frame, in.tex_coords);
+    let after = textureSample(t_to, s_frame, in.tex_coords);
+    let t = params.progress;
+    switch params.mode {
+        case 0u: {
+            if t < 0.5 {
+                return mix(before, params.color, t * 2.0);
+            }
+            return mix(params.color, after, t * 2.0 - 1.0);
+        }
+        case 1u: {
+            return mix(before, after, t);
+        }
+        default: {
+            // 0 where the edge starts and 1 where it finishes
+            let along = dot(in.tex_coords - 0.5, params.direction) + 0.5;
+            let edge = t * (1.0 + 2.0 * WIPE_EDGE) - WIPE_EDGE;
+            return mix(after, before, smoothstep(edge - WIPE_EDGE, edge + WIPE_EDGE, along));
+        }
+    }
+}